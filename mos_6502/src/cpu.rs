@@ -2,9 +2,10 @@ use crate::{
     debugging::{Debugger, ExecutionState},
     memory::Bus16,
 };
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{Arc, Mutex};
 
 /// A MOS 6502 CPU
+#[derive(Clone)]
 pub struct CPU {
     pub a: u8,
     pub x: u8,
@@ -21,12 +22,13 @@ pub struct CPU {
     pub negative: bool,
 
     pub nmi: bool,
-    last_nmi: bool,
+    /// The NMI input as of the last instruction, which edge detection compares against.
+    pub last_nmi: bool,
     pub irq: bool,
 
     pub total_cycles: u64,
     pub jammed: bool,
-    debugger: Option<Rc<RefCell<Debugger>>>,
+    debugger: Option<Arc<Mutex<Debugger>>>,
 }
 
 impl CPU {
@@ -58,7 +60,7 @@ impl CPU {
         }
     }
 
-    pub fn attach_debugger(&mut self, debugger: Rc<RefCell<Debugger>>) {
+    pub fn attach_debugger(&mut self, debugger: Arc<Mutex<Debugger>>) {
         self.debugger = Some(debugger);
     }
 
@@ -74,6 +76,10 @@ impl CPU {
         self.encode_p(false)
     }
 
+    pub fn set_status_register(&mut self, p: u8) {
+        self.decode_p(p);
+    }
+
     pub fn reset(&mut self, bus: &mut dyn Bus16) {
         self.pc = bus.read_word(Self::RESET_VECTOR);
         self.s = 0xFD;
@@ -87,18 +93,25 @@ impl CPU {
         }
 
         if let Some(debugger) = &self.debugger {
-            debugger.borrow_mut().record_state(self.current_state(bus));
+            debugger
+                .lock()
+                .unwrap()
+                .record_state(self.current_state(bus));
         }
 
         let cycles_at_start = self.total_cycles;
 
-        if self.nmi && !self.last_nmi {
+        // Servicing an interrupt takes the place of an instruction, so a caller stepping the CPU sees it separately.
+        let nmi_edge = self.nmi && !self.last_nmi;
+        self.last_nmi = self.nmi;
+        if nmi_edge {
             self.nmi(bus);
+            return self.total_cycles - cycles_at_start;
         }
-        self.last_nmi = self.nmi;
 
         if self.irq && !self.irq_disable {
             self.irq(bus);
+            return self.total_cycles - cycles_at_start;
         }
 
         let opcode = bus.read_byte(self.pc);
@@ -1042,6 +1055,13 @@ impl CPU {
         self.pc.wrapping_add_signed(offset)
     }
 
+    /// Read-modify-write instructions write the unmodified value straight back while they work out the result, then
+    /// write the result over it.
+    fn write_modified(bus: &mut dyn Bus16, address: u16, value: u8, result: u8) {
+        bus.write_byte(address, value);
+        bus.write_byte(address, result);
+    }
+
     fn push_byte(&mut self, bus: &mut dyn Bus16, value: u8) {
         bus.write_byte(Self::STACK_BASE + self.s as u16, value);
         self.s = self.s.wrapping_sub(1);
@@ -1173,14 +1193,18 @@ impl CPU {
         )
     }
 
-    // Operation ADC: Add memory to accumulator with carry.
-    fn adc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        let value = bus.read_byte(address);
+    fn add_to_accumulator(&mut self, value: u8) {
         let (sum, carry, overflow) = CPU::adder(self.a, value, self.carry);
         self.a = sum;
         self.carry = carry;
         self.overflow = overflow;
         self.set_nz_flags(self.a);
+    }
+
+    // Operation ADC: Add memory to accumulator with carry.
+    fn adc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
+        let value = bus.read_byte(address);
+        self.add_to_accumulator(value);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1189,11 +1213,7 @@ impl CPU {
     // Operation SBC: Subtract memory from accumulator with borrow.
     fn sbc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
         let value = bus.read_byte(address);
-        let (sum, carry, overflow) = CPU::adder(self.a, !value, self.carry);
-        self.a = sum;
-        self.carry = carry;
-        self.overflow = overflow;
-        self.set_nz_flags(self.a);
+        self.add_to_accumulator(!value);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1241,7 +1261,7 @@ impl CPU {
     }
 
     // Operation ASL: Shift left one bit (memory or accumulator).
-    fn asl(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) {
+    fn asl(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) -> u8 {
         let value = match address {
             Some(address) => bus.read_byte(address),
             None => self.a,
@@ -1252,16 +1272,17 @@ impl CPU {
         self.carry = value & (1 << 7) != 0;
 
         match address {
-            Some(address) => bus.write_byte(address, result),
+            Some(address) => CPU::write_modified(bus, address, value, result),
             None => self.a = result,
         }
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation LSR: Shift right one bit (memory or accumulator).
-    fn lsr(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) {
+    fn lsr(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) -> u8 {
         let value = match address {
             Some(address) => bus.read_byte(address),
             None => self.a,
@@ -1272,16 +1293,17 @@ impl CPU {
         self.carry = value & (1 << 0) != 0;
 
         match address {
-            Some(address) => bus.write_byte(address, result),
+            Some(address) => CPU::write_modified(bus, address, value, result),
             None => self.a = result,
         }
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation ROL: Rotate left one bit (memory or accumulator).
-    fn rol(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) {
+    fn rol(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) -> u8 {
         let value = match address {
             Some(address) => bus.read_byte(address),
             None => self.a,
@@ -1292,16 +1314,17 @@ impl CPU {
         self.set_nz_flags(result);
 
         match address {
-            Some(address) => bus.write_byte(address, result),
+            Some(address) => CPU::write_modified(bus, address, value, result),
             None => self.a = result,
         }
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation ROR: Rotate right one bit (memory or accumulator).
-    fn ror(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) {
+    fn ror(&mut self, bus: &mut dyn Bus16, address: Option<u16>, length: u16, cycles: u64) -> u8 {
         let value = match address {
             Some(address) => bus.read_byte(address),
             None => self.a,
@@ -1312,12 +1335,13 @@ impl CPU {
         self.set_nz_flags(result);
 
         match address {
-            Some(address) => bus.write_byte(address, result),
+            Some(address) => CPU::write_modified(bus, address, value, result),
             None => self.a = result,
         }
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation JMP: Jump to new location.
@@ -1445,25 +1469,27 @@ impl CPU {
     }
 
     // Operation INC: Increment memory by one.
-    fn inc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
+    fn inc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) -> u8 {
         let value = bus.read_byte(address);
         let result = value.wrapping_add(1);
         self.set_nz_flags(result);
-        bus.write_byte(address, result);
+        CPU::write_modified(bus, address, value, result);
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation DEC: Decrement memory by one.
-    fn dec(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
+    fn dec(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) -> u8 {
         let value = bus.read_byte(address);
         let result = value.wrapping_sub(1);
         self.set_nz_flags(result);
-        bus.write_byte(address, result);
+        CPU::write_modified(bus, address, value, result);
 
         self.pc += length;
         self.total_cycles += cycles;
+        result
     }
 
     // Operation INX: Increment index X by one.
@@ -1690,8 +1716,8 @@ impl CPU {
 
     // "Illegal" operation DCP: DEC + CMP
     fn dcp(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.dec(bus, address, 0, 0);
-        self.cmp(bus, address, 0, 0);
+        let value = self.dec(bus, address, 0, 0);
+        self.compare_value(self.a, value);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1699,8 +1725,8 @@ impl CPU {
 
     // "Illegal" operation ISC: INC + SBC
     fn isc(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.inc(bus, address, 0, 0);
-        self.sbc(bus, address, 0, 0);
+        let value = self.inc(bus, address, 0, 0);
+        self.add_to_accumulator(!value);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1708,8 +1734,9 @@ impl CPU {
 
     // "Illegal" operation SLO: ASL + ORA
     fn slo(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.asl(bus, Some(address), 0, 0);
-        self.ora(bus, address, 0, 0);
+        let value = self.asl(bus, Some(address), 0, 0);
+        self.a |= value;
+        self.set_nz_flags(self.a);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1717,8 +1744,9 @@ impl CPU {
 
     // "Illegal" operation RLA: ROL + AND
     fn rla(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.rol(bus, Some(address), 0, 0);
-        self.and(bus, address, 0, 0);
+        let value = self.rol(bus, Some(address), 0, 0);
+        self.a &= value;
+        self.set_nz_flags(self.a);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1726,8 +1754,9 @@ impl CPU {
 
     // "Illegal" operation SRE: LSR + EOR
     fn sre(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.lsr(bus, Some(address), 0, 0);
-        self.eor(bus, address, 0, 0);
+        let value = self.lsr(bus, Some(address), 0, 0);
+        self.a ^= value;
+        self.set_nz_flags(self.a);
 
         self.pc += length;
         self.total_cycles += cycles;
@@ -1735,8 +1764,8 @@ impl CPU {
 
     // "Illegal" operation RRA: ROR + ADC
     fn rra(&mut self, bus: &mut dyn Bus16, address: u16, length: u16, cycles: u64) {
-        self.ror(bus, Some(address), 0, 0);
-        self.adc(bus, address, 0, 0);
+        let value = self.ror(bus, Some(address), 0, 0);
+        self.add_to_accumulator(value);
 
         self.pc += length;
         self.total_cycles += cycles;
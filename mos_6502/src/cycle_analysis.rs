@@ -0,0 +1,1145 @@
+use crate::{
+    disassembly::{AddressingMode, Instruction, Mnemonic},
+    memory::Bus16,
+};
+use std::{collections::BTreeMap, fmt::Display, ops::RangeInclusive};
+
+/// An inclusive range of cycle counts. An upper bound of `None` means the count depends on data that can't be known
+/// statically (e.g. a loop polling a hardware register).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleRange {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl CycleRange {
+    pub fn exact(cycles: u64) -> Self {
+        Self {
+            min: cycles,
+            max: Some(cycles),
+        }
+    }
+
+    pub fn new(min: u64, max: u64) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.max == Some(self.min)
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            min: self.min + other.min,
+            max: self.max.zip(other.max).map(|(a, b)| a + b),
+        }
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            min: self.min.saturating_sub(other.min),
+            max: self.max.zip(other.max).map(|(a, b)| a.saturating_sub(b)),
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.zip(other.max).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    fn unbounded(self) -> Self {
+        Self {
+            min: self.min,
+            max: None,
+        }
+    }
+}
+
+impl Display for CycleRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self.max {
+            Some(max) if max == self.min => format!("{}", self.min),
+            Some(max) => format!("{}-{}", self.min, max),
+            None => format!("{}+", self.min),
+        };
+        f.pad(&text)
+    }
+}
+
+/// Facts about the code being analyzed that can't be derived from the image alone.
+pub struct AnalysisOptions {
+    /// Values the X register may hold wherever the analyzer can't track it.
+    pub x_range: RangeInclusive<u8>,
+    /// Values the Y register may hold wherever the analyzer can't track it.
+    pub y_range: RangeInclusive<u8>,
+    /// Stop forking at branches once this many paths have been found.
+    pub max_paths: usize,
+    /// Stop following a single path after this many instructions.
+    pub max_path_length: usize,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            x_range: 0..=255,
+            y_range: 0..=255,
+            max_paths: 256,
+            max_path_length: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathExit {
+    Return,
+    ReturnFromInterrupt,
+    Break,
+    Jam,
+    IndirectJump,
+    InfiniteLoop,
+    Truncated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTiming {
+    pub start: u16,
+    pub end: u16,
+    pub exit: PathExit,
+    pub cycles: CycleRange,
+    pub taken_branches: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+    /// The loop is controlled by an index register with a known starting value.
+    Counted { iterations: u16 },
+    /// The number of iterations depends on memory, flags, or registers the analyzer can't track.
+    DataDependent,
+    /// The loop has no exit (e.g. `JMP *` while waiting for NMI).
+    Infinite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopTiming {
+    pub head: u16,
+    pub back_edge: u16,
+    pub kind: LoopKind,
+    /// Cycles for one trip through the body, including the branch back to the head.
+    pub iteration_cycles: CycleRange,
+}
+
+struct ListingLine {
+    instruction: Instruction,
+    cycles: CycleRange,
+    taken_branch_cycles: Option<u64>,
+}
+
+/// The result of a static timing analysis: per-path cycle counts, the loops found along the way, and an annotated
+/// listing of every instruction visited.
+pub struct TimingReport {
+    pub entry: u16,
+    pub paths: Vec<PathTiming>,
+    pub loops: Vec<LoopTiming>,
+    pub truncated: bool,
+    listing: BTreeMap<u16, ListingLine>,
+}
+
+impl TimingReport {
+    pub fn cycles(&self) -> Option<CycleRange> {
+        self.paths
+            .iter()
+            .map(|path| path.cycles)
+            .reduce(CycleRange::union)
+    }
+
+    pub fn data_dependent_loops(&self) -> impl Iterator<Item = &LoopTiming> {
+        self.loops
+            .iter()
+            .filter(|l| l.kind == LoopKind::DataDependent)
+    }
+
+    fn loop_note(&self, address: u16) -> Option<String> {
+        let l = self.loops.iter().find(|l| l.back_edge == address)?;
+        let note = match l.kind {
+            LoopKind::Counted { iterations } => format!(
+                "loop ${:04X} x{} ({} per iteration)",
+                l.head, iterations, l.iteration_cycles
+            ),
+            LoopKind::DataDependent => format!(
+                "loop ${:04X} DATA DEPENDENT ({} per iteration)",
+                l.head, l.iteration_cycles
+            ),
+            LoopKind::Infinite => format!("loop ${:04X} never exits", l.head),
+        };
+        Some(note)
+    }
+}
+
+impl Display for TimingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (address, line) in &self.listing {
+            let cycles = match line.taken_branch_cycles {
+                Some(taken) => format!("{}/{}", line.cycles, taken),
+                None => format!("{}", line.cycles),
+            };
+            let note = self.loop_note(*address).unwrap_or_default();
+            writeln!(
+                f,
+                "{:04X}  {:<28}  {:>7}  {}",
+                address, line.instruction, cycles, note
+            )?;
+        }
+
+        writeln!(f)?;
+        for (i, path) in self.paths.iter().enumerate() {
+            writeln!(
+                f,
+                "path {}: ${:04X} -> ${:04X} ({:?}) {} cycles",
+                i, path.start, path.end, path.exit, path.cycles
+            )?;
+        }
+        if self.truncated {
+            writeln!(f, "analysis truncated: limits reached")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    None,
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+fn access(mnemonic: Mnemonic) -> Access {
+    use Mnemonic::*;
+    match mnemonic {
+        ADC | ALR | ANC | AND | ARR | BIT | CMP | CPX | CPY | EOR | LAS | LAX | LDA | LDX | LDY
+        | LXA | NOP | ORA | SBC | SBX | XAA => Access::Read,
+        SAX | SHA | SHX | SHY | STA | STX | STY | TAS => Access::Write,
+        ASL | DCP | DEC | INC | ISC | LSR | RLA | ROL | ROR | RRA | SLO | SRE => {
+            Access::ReadModifyWrite
+        }
+        _ => Access::None,
+    }
+}
+
+/// The number of cycles an instruction takes, not counting page crossings or taken branches.
+pub fn base_cycles(instruction: &Instruction) -> u64 {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    match (instruction.mnemonic(), instruction.addressing_mode()) {
+        (BRK, _) => 7,
+        (RTI | RTS | JSR, _) => 6,
+        (PHA | PHP, _) => 3,
+        (PLA | PLP, _) => 4,
+        (JMP, Absolute) => 3,
+        (JMP, _) => 5,
+        (JAM, _) => 0,
+        (_, Implied | Accumulator | Immediate | Relative) => 2,
+        (mnemonic, addressing_mode) => match (access(mnemonic), addressing_mode) {
+            (Access::ReadModifyWrite, ZeroPage) => 5,
+            (Access::ReadModifyWrite, ZeroPageX | ZeroPageY | Absolute) => 6,
+            (Access::ReadModifyWrite, AbsoluteX | AbsoluteY) => 7,
+            (Access::ReadModifyWrite, IndirectX | IndirectY) => 8,
+            (_, ZeroPage) => 3,
+            (_, ZeroPageX | ZeroPageY | Absolute) => 4,
+            (Access::Write, AbsoluteX | AbsoluteY) => 5,
+            (_, AbsoluteX | AbsoluteY) => 4,
+            (_, IndirectX) => 6,
+            (Access::Write, IndirectY) => 6,
+            (_, IndirectY) => 5,
+            _ => unreachable!(),
+        },
+    }
+}
+
+/// Whether an instruction takes an extra cycle when its indexed effective address crosses a page.
+pub fn has_page_cross_penalty(instruction: &Instruction) -> bool {
+    use AddressingMode::*;
+    access(instruction.mnemonic()) == Access::Read
+        && matches!(
+            instruction.addressing_mode(),
+            AbsoluteX | AbsoluteY | IndirectY
+        )
+}
+
+/// The cycles, counted from 0 at the opcode fetch, on which an instruction makes each of its bus accesses, in the
+/// order it makes them: the opcode and operand fetches, any pointer fetches, then its effective address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessCycles {
+    cycles: [u64; 7],
+    len: usize,
+}
+
+impl AccessCycles {
+    fn push(&mut self, cycle: u64) {
+        self.cycles[self.len] = cycle;
+        self.len += 1;
+    }
+
+    /// The cycle of the instruction's `access`th bus access, counting from 0. Stack and interrupt vector accesses
+    /// aren't covered.
+    pub fn get(&self, access: u64) -> Option<u64> {
+        self.cycles[..self.len].get(access as usize).copied()
+    }
+}
+
+/// When an instruction makes each of its bus accesses. Operands and pointers are fetched a cycle apart, except that
+/// (zp,X) spends a cycle adding X to the pointer first. The effective address is always accessed on the last cycle,
+/// except by read-modify-write instructions, which read it two cycles before the end and write the unmodified value
+/// back in between.
+pub fn access_cycles(instruction: &Instruction, page_crossed: bool) -> AccessCycles {
+    use AddressingMode::*;
+
+    let addressing_mode = instruction.addressing_mode();
+    let fetches: &[u64] = match addressing_mode {
+        Implied | Accumulator | Immediate | Relative => &[0],
+        ZeroPage | ZeroPageX | ZeroPageY => &[0, 1],
+        Absolute | AbsoluteX | AbsoluteY => &[0, 1, 2],
+        IndirectX => &[0, 1, 3, 4],
+        IndirectY => &[0, 1, 2, 3],
+        Indirect => &[0, 1, 2, 3, 4],
+    };
+    let mut cycles = AccessCycles::default();
+    fetches.iter().for_each(|&cycle| cycles.push(cycle));
+    if matches!(addressing_mode, Implied | Accumulator | Relative) {
+        return cycles;
+    }
+
+    let penalty = (page_crossed && has_page_cross_penalty(instruction)) as u64;
+    let last_cycle = base_cycles(instruction) + penalty - 1;
+    match access(instruction.mnemonic()) {
+        Access::None => {}
+        Access::Read | Access::Write => cycles.push(last_cycle),
+        Access::ReadModifyWrite => {
+            cycles.push(last_cycle - 2);
+            cycles.push(last_cycle - 1);
+            cycles.push(last_cycle);
+        }
+    }
+    cycles
+}
+
+/// Whether an instruction's indexed effective address lands on a different page than its base address, given the
+/// current index registers.
+pub fn crosses_page(bus: &dyn Bus16, instruction: &Instruction, x: u8, y: u8) -> bool {
+    use AddressingMode::*;
+
+    match instruction.addressing_mode() {
+        AbsoluteX => {
+            let base_address = operand_word(instruction);
+            crosses_page_boundary(base_address, base_address.wrapping_add(x as u16))
+        }
+        AbsoluteY => {
+            let base_address = operand_word(instruction);
+            crosses_page_boundary(base_address, base_address.wrapping_add(y as u16))
+        }
+        IndirectY => {
+            let pointer = instruction.operand1;
+            let low_byte = bus.peek_byte(pointer as u16);
+            let high_byte = bus.peek_byte(pointer.wrapping_add(1) as u16);
+            let base_address = (high_byte as u16) << 8 | low_byte as u16;
+            crosses_page_boundary(base_address, base_address.wrapping_add(y as u16))
+        }
+        _ => false,
+    }
+}
+
+fn crosses_page_boundary(a: u16, b: u16) -> bool {
+    a & 0xFF00 != b & 0xFF00
+}
+
+fn fetch_instruction(bus: &dyn Bus16, address: u16) -> Instruction {
+    Instruction::new(
+        bus.peek_byte(address),
+        bus.peek_byte(address.wrapping_add(1)),
+        bus.peek_byte(address.wrapping_add(2)),
+    )
+}
+
+fn operand_word(instruction: &Instruction) -> u16 {
+    (instruction.operand2 as u16) << 8 | instruction.operand1 as u16
+}
+
+fn branch_target(instruction: &Instruction, address: u16) -> u16 {
+    let next = address.wrapping_add(2);
+    next.wrapping_add_signed(instruction.operand1 as i8 as i16)
+}
+
+#[derive(Clone, Copy)]
+enum Register {
+    X,
+    Y,
+}
+
+fn writes_register(mnemonic: Mnemonic, register: Register) -> bool {
+    use Mnemonic::*;
+    match register {
+        Register::X => matches!(
+            mnemonic,
+            LDX | LAX | TAX | TSX | INX | DEX | SBX | LXA | LAS
+        ),
+        Register::Y => matches!(mnemonic, LDY | TAY | INY | DEY),
+    }
+}
+
+fn is_branch(mnemonic: Mnemonic) -> bool {
+    use Mnemonic::*;
+    matches!(mnemonic, BCC | BCS | BEQ | BMI | BNE | BPL | BVC | BVS)
+}
+
+#[derive(Clone)]
+struct TrailEntry {
+    address: u16,
+    cycles_before: CycleRange,
+    x: Option<u8>,
+    y: Option<u8>,
+}
+
+#[derive(Clone)]
+struct PathState {
+    start: u16,
+    pc: u16,
+    cycles: CycleRange,
+    x: Option<u8>,
+    y: Option<u8>,
+    call_stack: Vec<u16>,
+    trail: Vec<TrailEntry>,
+    /// Where in the trail the current subroutine's instructions start, so loop heads are only looked for in the
+    /// current call. An earlier call to the same subroutine isn't a loop.
+    frame_start: usize,
+    frame_starts: Vec<usize>,
+    taken_branches: Vec<u16>,
+}
+
+impl PathState {
+    /// The position in the trail of an earlier visit to `address` in the current call, if any.
+    fn loop_head(&self, address: u16) -> Option<usize> {
+        self.trail[self.frame_start..]
+            .iter()
+            .position(|e| e.address == address)
+            .map(|position| self.frame_start + position)
+    }
+
+    fn set_register(&mut self, register: Register, value: Option<u8>) {
+        match register {
+            Register::X => self.x = value,
+            Register::Y => self.y = value,
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    bus: &'a dyn Bus16,
+    options: &'a AnalysisOptions,
+    listing: BTreeMap<u16, ListingLine>,
+    paths: Vec<PathTiming>,
+    loops: Vec<LoopTiming>,
+    truncated: bool,
+}
+
+/// Walks the control-flow graph of the code in `bus` starting at `entry` and reports the cycle count of every path
+/// through it. Subroutine calls are followed; a path ends when it returns from the entry routine or leaves the
+/// analyzer's view (indirect jumps, BRK, JAM).
+pub fn analyze(bus: &dyn Bus16, entry: u16, options: &AnalysisOptions) -> TimingReport {
+    let mut analyzer = Analyzer {
+        bus,
+        options,
+        listing: BTreeMap::new(),
+        paths: Vec::new(),
+        loops: Vec::new(),
+        truncated: false,
+    };
+
+    let mut work_list = vec![PathState {
+        start: entry,
+        pc: entry,
+        cycles: CycleRange::exact(0),
+        x: None,
+        y: None,
+        call_stack: Vec::new(),
+        trail: Vec::new(),
+        frame_start: 0,
+        frame_starts: Vec::new(),
+        taken_branches: Vec::new(),
+    }];
+    while let Some(state) = work_list.pop() {
+        analyzer.walk(state, &mut work_list);
+    }
+
+    TimingReport {
+        entry,
+        paths: analyzer.paths,
+        loops: analyzer.loops,
+        truncated: analyzer.truncated,
+        listing: analyzer.listing,
+    }
+}
+
+impl<'a> Analyzer<'a> {
+    fn walk(&mut self, mut state: PathState, work_list: &mut Vec<PathState>) {
+        loop {
+            if state.trail.len() >= self.options.max_path_length {
+                self.truncated = true;
+                return self.finish(state, PathExit::Truncated);
+            }
+
+            let address = state.pc;
+            let instruction = fetch_instruction(self.bus, address);
+            state.trail.push(TrailEntry {
+                address,
+                cycles_before: state.cycles,
+                x: state.x,
+                y: state.y,
+            });
+
+            let cost = self.instruction_cost(&instruction, state.x, state.y);
+            state.cycles = state.cycles.add(cost);
+            self.record(address, &instruction, cost, None);
+
+            use Mnemonic::*;
+            match instruction.mnemonic() {
+                JMP => match instruction.addressing_mode() {
+                    AddressingMode::Absolute => {
+                        let target = operand_word(&instruction);
+                        if let Some(head) = state.loop_head(target) {
+                            let iteration_cycles =
+                                state.cycles.sub(state.trail[head].cycles_before);
+                            if self.release_loop_exits(&state, head, work_list) {
+                                let kind = LoopKind::DataDependent;
+                                self.record_loop(target, address, kind, iteration_cycles);
+                                return;
+                            }
+                            self.record_loop(target, address, LoopKind::Infinite, iteration_cycles);
+                            state.cycles = state.cycles.unbounded();
+                            return self.finish(state, PathExit::InfiniteLoop);
+                        }
+                        state.pc = target;
+                    }
+                    _ => return self.finish(state, PathExit::IndirectJump),
+                },
+                JSR => {
+                    state.call_stack.push(address.wrapping_add(3));
+                    state.frame_starts.push(state.frame_start);
+                    state.frame_start = state.trail.len();
+                    state.pc = operand_word(&instruction);
+                }
+                RTS => match state.call_stack.pop() {
+                    Some(return_address) => {
+                        state.frame_start = state.frame_starts.pop().unwrap_or(0);
+                        state.pc = return_address;
+                    }
+                    None => return self.finish(state, PathExit::Return),
+                },
+                RTI => return self.finish(state, PathExit::ReturnFromInterrupt),
+                BRK => return self.finish(state, PathExit::Break),
+                JAM => return self.finish(state, PathExit::Jam),
+                mnemonic if is_branch(mnemonic) => {
+                    let fall_through = address.wrapping_add(2);
+                    let target = branch_target(&instruction, address);
+                    let taken_penalty = 1 + crosses_page_boundary(fall_through, target) as u64;
+                    self.record(address, &instruction, cost, Some(cost.min + taken_penalty));
+
+                    if let Some(head) = state.loop_head(target) {
+                        self.close_loop(&mut state, head, &instruction, taken_penalty);
+                    } else if self.paths.len() + work_list.len() + 1 < self.options.max_paths {
+                        let mut taken = state.clone();
+                        taken.cycles = taken.cycles.add(CycleRange::exact(taken_penalty));
+                        taken.taken_branches.push(address);
+                        taken.pc = target;
+                        work_list.push(taken);
+                    } else {
+                        self.truncated = true;
+                    }
+                    state.pc = fall_through;
+                }
+                mnemonic => {
+                    Self::update_registers(&mut state, mnemonic, &instruction);
+                    state.pc = address.wrapping_add(instruction.length() as u16);
+                }
+            }
+        }
+    }
+
+    fn instruction_cost(
+        &self,
+        instruction: &Instruction,
+        x: Option<u8>,
+        y: Option<u8>,
+    ) -> CycleRange {
+        let base = base_cycles(instruction);
+        if !has_page_cross_penalty(instruction) {
+            return CycleRange::exact(base);
+        }
+
+        let (index, assumed) = match instruction.addressing_mode() {
+            AddressingMode::AbsoluteX => (x, &self.options.x_range),
+            _ => (y, &self.options.y_range),
+        };
+        let (low, high) = match index {
+            Some(value) => (value, value),
+            None => (*assumed.start(), *assumed.end()),
+        };
+
+        match instruction.addressing_mode() {
+            AddressingMode::IndirectY if high > 0 => {
+                // The pointer lives in RAM, so its alignment isn't known.
+                CycleRange::new(base, base + 1)
+            }
+            AddressingMode::IndirectY => CycleRange::exact(base),
+            _ => {
+                let base_address = operand_word(instruction);
+                let min_penalty =
+                    crosses_page_boundary(base_address, base_address.wrapping_add(low as u16));
+                let max_penalty =
+                    crosses_page_boundary(base_address, base_address.wrapping_add(high as u16));
+                CycleRange::new(base + min_penalty as u64, base + max_penalty as u64)
+            }
+        }
+    }
+
+    fn update_registers(state: &mut PathState, mnemonic: Mnemonic, instruction: &Instruction) {
+        use Mnemonic::*;
+        let immediate = matches!(instruction.addressing_mode(), AddressingMode::Immediate);
+        match mnemonic {
+            LDX if immediate => state.x = Some(instruction.operand1),
+            LDY if immediate => state.y = Some(instruction.operand1),
+            INX => state.x = state.x.map(|x| x.wrapping_add(1)),
+            DEX => state.x = state.x.map(|x| x.wrapping_sub(1)),
+            INY => state.y = state.y.map(|y| y.wrapping_add(1)),
+            DEY => state.y = state.y.map(|y| y.wrapping_sub(1)),
+            mnemonic => {
+                if writes_register(mnemonic, Register::X) {
+                    state.x = None;
+                }
+                if writes_register(mnemonic, Register::Y) {
+                    state.y = None;
+                }
+            }
+        }
+    }
+
+    /// Handles a conditional branch back to an instruction already on the current path. The body has been walked once;
+    /// counted loops get the remaining iterations added, other loops make the path's upper bound unknown.
+    fn close_loop(
+        &mut self,
+        state: &mut PathState,
+        head: usize,
+        branch: &Instruction,
+        taken_penalty: u64,
+    ) {
+        let head_entry = state.trail[head].clone();
+        let back_edge = state.trail.last().unwrap().address;
+        let iteration_cycles = state
+            .cycles
+            .sub(head_entry.cycles_before)
+            .add(CycleRange::exact(taken_penalty));
+
+        let body: Vec<(Mnemonic, Instruction)> = state.trail[head..state.trail.len() - 1]
+            .iter()
+            .map(|e| fetch_instruction(self.bus, e.address))
+            .map(|i| (i.mnemonic(), i))
+            .collect();
+
+        match Self::count_iterations(&body, branch.mnemonic(), &head_entry) {
+            Some((register, iterations, final_value)) => {
+                // The walk covered the first iteration. Each later one is costed with the index registers it will
+                // actually see, since indexed accesses may only cross a page on some iterations.
+                let mut extra = CycleRange::exact(0);
+                let mut per_iteration = iteration_cycles;
+                for iteration in 1..iterations {
+                    let cycles = self
+                        .iteration_cost(&state.trail[head..], &body, iteration)
+                        .add(CycleRange::exact(base_cycles(branch) + taken_penalty));
+                    per_iteration = per_iteration.union(cycles);
+                    extra = extra.add(cycles);
+                }
+
+                let kind = LoopKind::Counted { iterations };
+                self.record_loop(head_entry.address, back_edge, kind, per_iteration);
+                state.cycles = state.cycles.add(extra);
+                state.set_register(register, Some(final_value));
+            }
+            None => {
+                let kind = LoopKind::DataDependent;
+                self.record_loop(head_entry.address, back_edge, kind, iteration_cycles);
+                state.cycles = state.cycles.unbounded();
+                for (mnemonic, _) in &body {
+                    for register in [Register::X, Register::Y] {
+                        if writes_register(*mnemonic, register) {
+                            state.set_register(register, None);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles a `JMP` back to an instruction already on the current path. A conditional branch in the body whose
+    /// target lies outside it was forked onto the work list before the loop was seen; those paths leave after an
+    /// unknown number of iterations, so their upper bound becomes unknown. Returns whether the loop has such an exit.
+    fn release_loop_exits(
+        &self,
+        state: &PathState,
+        head: usize,
+        work_list: &mut [PathState],
+    ) -> bool {
+        let body = &state.trail[head..];
+        let instructions: Vec<Instruction> = body
+            .iter()
+            .map(|e| fetch_instruction(self.bus, e.address))
+            .collect();
+
+        let mut exits = false;
+        for (position, (entry, instruction)) in body.iter().zip(&instructions).enumerate() {
+            if !is_branch(instruction.mnemonic()) {
+                continue;
+            }
+            let target = branch_target(instruction, entry.address);
+            if body.iter().any(|e| e.address == target) {
+                continue;
+            }
+            exits = true;
+
+            // The taken side of this branch, forked from this very path.
+            let fork_length = head + position + 1;
+            let forks = work_list.iter_mut().filter(|fork| {
+                fork.taken_branches.last() == Some(&entry.address)
+                    && fork.trail.len() == fork_length
+                    && fork
+                        .trail
+                        .iter()
+                        .zip(&state.trail)
+                        .all(|(a, b)| a.address == b.address)
+            });
+            for fork in forks {
+                fork.cycles = fork.cycles.unbounded();
+                for instruction in &instructions {
+                    for register in [Register::X, Register::Y] {
+                        if writes_register(instruction.mnemonic(), register) {
+                            fork.set_register(register, None);
+                        }
+                    }
+                }
+            }
+        }
+        exits
+    }
+
+    /// The cost of a loop body, without the branch back, on the given iteration of a counted loop. `trail` starts with
+    /// the first iteration's instructions and the registers they saw. A register the body only increments and
+    /// decrements moves on by the same amount each time round; one it writes any other way is treated as unknown.
+    fn iteration_cost(
+        &self,
+        trail: &[TrailEntry],
+        body: &[(Mnemonic, Instruction)],
+        iteration: u16,
+    ) -> CycleRange {
+        let step = |register: Register| -> Option<i32> {
+            let mut step = 0;
+            for (mnemonic, _) in body {
+                match (mnemonic, register) {
+                    (Mnemonic::INX, Register::X) | (Mnemonic::INY, Register::Y) => step += 1,
+                    (Mnemonic::DEX, Register::X) | (Mnemonic::DEY, Register::Y) => step -= 1,
+                    (mnemonic, register) if writes_register(*mnemonic, register) => return None,
+                    _ => {}
+                }
+            }
+            Some(step)
+        };
+        let shift = |value: Option<u8>, step: Option<i32>| -> Option<u8> {
+            let offset = step? * iteration as i32;
+            value.map(|value| (value as i32 + offset) as u8)
+        };
+        let (x_step, y_step) = (step(Register::X), step(Register::Y));
+
+        trail
+            .iter()
+            .zip(body)
+            .map(|(entry, (_, instruction))| {
+                self.instruction_cost(instruction, shift(entry.x, x_step), shift(entry.y, y_step))
+            })
+            .fold(CycleRange::exact(0), CycleRange::add)
+    }
+
+    /// Recognizes the common index-register loop shapes (`DEX; BNE`, `DEY; BPL`, `INX; CPX #n; BNE`, ...) and returns
+    /// the counting register, the number of iterations and the register's value after the loop.
+    fn count_iterations(
+        body: &[(Mnemonic, Instruction)],
+        branch: Mnemonic,
+        head: &TrailEntry,
+    ) -> Option<(Register, u16, u8)> {
+        use Mnemonic::*;
+
+        let (compare, step) = match body {
+            [.., (step, _), (CPX | CPY, compare)] => (Some(compare), *step),
+            [.., (step, _)] => (None, *step),
+            [] => return None,
+        };
+        let (register, increment) = match step {
+            INX => (Register::X, true),
+            DEX => (Register::X, false),
+            INY => (Register::Y, true),
+            DEY => (Register::Y, false),
+            _ => return None,
+        };
+        let writes = body
+            .iter()
+            .filter(|(mnemonic, _)| writes_register(*mnemonic, register))
+            .count();
+        if writes != 1 {
+            return None;
+        }
+        let start = match register {
+            Register::X => head.x,
+            Register::Y => head.y,
+        }?;
+
+        let count = |n: u8| if n == 0 { 256 } else { n as u16 };
+        match (compare, branch) {
+            (Some(compare), BNE) => {
+                let compare_register = match compare.mnemonic() {
+                    CPX => Register::X,
+                    _ => Register::Y,
+                };
+                let immediate = matches!(compare.addressing_mode(), AddressingMode::Immediate);
+                if !immediate
+                    || !matches!(
+                        (compare_register, register),
+                        (Register::X, Register::X) | (Register::Y, Register::Y)
+                    )
+                {
+                    return None;
+                }
+                let end = compare.operand1;
+                let iterations = if increment {
+                    count(end.wrapping_sub(start))
+                } else {
+                    count(start.wrapping_sub(end))
+                };
+                Some((register, iterations, end))
+            }
+            (None, BNE) if increment => Some((register, count(0u8.wrapping_sub(start)), 0)),
+            (None, BNE) => Some((register, count(start), 0)),
+            (None, BPL) if !increment && start < 0x80 => Some((register, start as u16 + 1, 0xFF)),
+            _ => None,
+        }
+    }
+
+    fn record(
+        &mut self,
+        address: u16,
+        instruction: &Instruction,
+        cycles: CycleRange,
+        taken_branch_cycles: Option<u64>,
+    ) {
+        match self.listing.get_mut(&address) {
+            Some(line) => {
+                line.cycles = line.cycles.union(cycles);
+                line.taken_branch_cycles = line.taken_branch_cycles.or(taken_branch_cycles);
+            }
+            None => {
+                let instruction = Instruction::new(
+                    instruction.opcode,
+                    instruction.operand1,
+                    instruction.operand2,
+                );
+                self.listing.insert(
+                    address,
+                    ListingLine {
+                        instruction,
+                        cycles,
+                        taken_branch_cycles,
+                    },
+                );
+            }
+        }
+    }
+
+    fn record_loop(
+        &mut self,
+        head: u16,
+        back_edge: u16,
+        kind: LoopKind,
+        iteration_cycles: CycleRange,
+    ) {
+        let existing = self
+            .loops
+            .iter_mut()
+            .find(|l| l.head == head && l.back_edge == back_edge);
+        match existing {
+            Some(existing) => {
+                if existing.kind != kind {
+                    existing.kind = LoopKind::DataDependent;
+                }
+                existing.iteration_cycles = existing.iteration_cycles.union(iteration_cycles);
+            }
+            None => self.loops.push(LoopTiming {
+                head,
+                back_edge,
+                kind,
+                iteration_cycles,
+            }),
+        }
+    }
+
+    fn finish(&mut self, state: PathState, exit: PathExit) {
+        self.paths.push(PathTiming {
+            start: state.start,
+            end: state.pc,
+            exit,
+            cycles: state.cycles,
+            taken_branches: state.taken_branches,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cpu::CPU, memory::FlatMemory};
+
+    fn load(program: &[u8]) -> FlatMemory {
+        let mut memory = FlatMemory::new();
+        memory.load_code(program, 0x8000, Some(0x8000));
+        memory
+    }
+
+    fn run_until_jammed(memory: &mut FlatMemory) -> u64 {
+        let mut cpu = CPU::new();
+        cpu.reset(memory);
+        let start = cpu.total_cycles;
+        while !cpu.jammed {
+            cpu.execute_instruction(memory);
+        }
+        cpu.total_cycles - start
+    }
+
+    #[test]
+    fn base_cycles_match_cpu() {
+        for opcode in 0..=255u8 {
+            // Operands point at $0200 so that no indexed access crosses a page.
+            let mut memory = load(&[opcode, 0x00, 0x02]);
+            let instruction = fetch_instruction(&memory, 0x8000);
+            if matches!(instruction.mnemonic(), Mnemonic::JAM) {
+                continue;
+            }
+
+            let mut cpu = CPU::new();
+            cpu.reset(&mut memory);
+            // Keep every flag-based branch from being taken.
+            cpu.negative = true;
+            cpu.overflow = true;
+            cpu.carry = true;
+            cpu.zero = false;
+            if matches!(instruction.mnemonic(), Mnemonic::BNE) {
+                cpu.zero = true;
+            }
+            if matches!(
+                instruction.mnemonic(),
+                Mnemonic::BMI | Mnemonic::BVS | Mnemonic::BCS
+            ) {
+                cpu.negative = false;
+                cpu.overflow = false;
+                cpu.carry = false;
+            }
+
+            let cycles = cpu.execute_instruction(&mut memory);
+            assert_eq!(
+                base_cycles(&instruction),
+                cycles,
+                "opcode ${:02X} ({})",
+                opcode,
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn counted_loop_matches_execution() {
+        let program = [
+            0xA2, 0x05, // LDX #5
+            0xBD, 0xF0, 0x80, // loop: LDA $80F0,X
+            0xCA, // DEX
+            0xD0, 0xFA, // BNE loop
+            0x02, // JAM
+        ];
+        let mut memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        assert_eq!(report.paths.len(), 1);
+        assert!(report.data_dependent_loops().next().is_none());
+
+        assert_eq!(
+            report.cycles(),
+            Some(CycleRange::exact(run_until_jammed(&mut memory)))
+        );
+    }
+
+    #[test]
+    fn counted_loop_crossing_pages_on_some_iterations() {
+        let program = [
+            0xA2, 0x20, // LDX #$20
+            0xBD, 0xF0, 0x80, // loop: LDA $80F0,X
+            0xCA, // DEX
+            0xD0, 0xFA, // BNE loop
+            0x02, // JAM
+        ];
+        let mut memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        // X >= $10 crosses into $81xx, X < $10 doesn't.
+        assert_eq!(report.loops[0].iteration_cycles, CycleRange::new(9, 10));
+        assert_eq!(
+            report.cycles(),
+            Some(CycleRange::exact(run_until_jammed(&mut memory)))
+        );
+    }
+
+    #[test]
+    fn repeated_calls_are_not_loops() {
+        let program = [
+            0x20, 0x07, 0x80, // JSR sub
+            0x20, 0x07, 0x80, // JSR sub
+            0x02, // JAM
+            0xA5, 0x10, // sub: LDA $10
+            0xF0, 0x01, // BEQ skip
+            0xEA, // NOP
+            0x60, // skip: RTS
+        ];
+        let mut memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        assert!(report.loops.is_empty());
+        // Each call either skips the NOP or doesn't.
+        assert_eq!(report.cycles(), Some(CycleRange::new(36, 38)));
+        assert_eq!(run_until_jammed(&mut memory), 36);
+    }
+
+    #[test]
+    fn polling_loop_is_data_dependent() {
+        let program = [
+            0x2C, 0x02, 0x20, // wait: BIT $2002
+            0x10, 0xFB, // BPL wait
+            0x60, // RTS
+        ];
+        let memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        assert_eq!(
+            report.cycles(),
+            Some(CycleRange {
+                min: 6 + 6,
+                max: None
+            })
+        );
+        assert_eq!(report.data_dependent_loops().count(), 1);
+    }
+
+    #[test]
+    fn jump_closed_loop_with_an_exit_is_data_dependent() {
+        let program = [
+            0x2C, 0x02, 0x20, // wait: BIT $2002
+            0x30, 0x03, // BMI done
+            0x4C, 0x00, 0x80, // JMP wait
+            0x60, // done: RTS
+        ];
+        let memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        assert_eq!(report.paths.len(), 1);
+        assert_eq!(report.paths[0].exit, PathExit::Return);
+        assert_eq!(
+            report.cycles(),
+            Some(CycleRange {
+                min: 4 + 3 + 6,
+                max: None
+            })
+        );
+        let loops: Vec<_> = report.data_dependent_loops().collect();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].head, 0x8000);
+        assert_eq!(loops[0].iteration_cycles, CycleRange::exact(4 + 2 + 3));
+    }
+
+    #[test]
+    fn jump_to_itself_never_exits() {
+        let program = [
+            0x4C, 0x00, 0x80, // JMP *
+        ];
+        let memory = load(&program);
+
+        let report = analyze(&memory, 0x8000, &AnalysisOptions::default());
+        assert_eq!(report.paths.len(), 1);
+        assert_eq!(report.paths[0].exit, PathExit::InfiniteLoop);
+        assert_eq!(report.loops[0].kind, LoopKind::Infinite);
+    }
+
+    #[test]
+    fn branches_fork_paths() {
+        let program = [
+            0xA5, 0x10, // LDA $10
+            0xF0, 0x01, // BEQ skip
+            0xEA, // NOP
+            0xB9, 0xFF, 0x80, // skip: LDA $80FF,Y
+            0x60, // RTS
+        ];
+        let memory = load(&program);
+
+        let options = AnalysisOptions {
+            y_range: 0..=1,
+            ..Default::default()
+        };
+        let report = analyze(&memory, 0x8000, &options);
+        assert_eq!(report.paths.len(), 2);
+        assert_eq!(
+            report.cycles(),
+            Some(CycleRange::new(3 + 3 + 4 + 6, 3 + 2 + 2 + 5 + 6))
+        );
+    }
+
+    #[test]
+    fn access_cycles_follow_the_bus_accesses() {
+        let cycles = |instruction: &Instruction, page_crossed| {
+            let access_cycles = access_cycles(instruction, page_crossed);
+            (0..)
+                .map_while(|access| access_cycles.get(access))
+                .collect::<Vec<_>>()
+        };
+
+        let lda_absolute = Instruction::new(0xAD, 0x02, 0x20);
+        assert_eq!(cycles(&lda_absolute, false), [0, 1, 2, 3]);
+
+        let lda_absolute_x = Instruction::new(0xBD, 0xFF, 0x20);
+        assert_eq!(cycles(&lda_absolute_x, true), [0, 1, 2, 4]);
+
+        // Stores always take the extra cycle.
+        let sta_absolute_x = Instruction::new(0x9D, 0x00, 0x20);
+        assert_eq!(cycles(&sta_absolute_x, false), [0, 1, 2, 4]);
+
+        let inc_absolute = Instruction::new(0xEE, 0x07, 0x20);
+        assert_eq!(cycles(&inc_absolute, false), [0, 1, 2, 3, 4, 5]);
+
+        let lda_indirect_x = Instruction::new(0xA1, 0x10, 0x00);
+        assert_eq!(cycles(&lda_indirect_x, false), [0, 1, 3, 4, 5]);
+
+        let lda_immediate = Instruction::new(0xA9, 0x10, 0x00);
+        assert_eq!(cycles(&lda_immediate, false), [0, 1]);
+
+        let asl_accumulator = Instruction::new(0x0A, 0x00, 0x00);
+        assert_eq!(cycles(&asl_accumulator, false), [0]);
+
+        let memory = load(&[0xB1, 0x10]);
+        let lda_indirect_y = fetch_instruction(&memory, 0x8000);
+        assert!(!crosses_page(&memory, &lda_indirect_y, 0, 0xFF));
+    }
+}
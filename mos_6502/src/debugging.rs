@@ -1,6 +1,7 @@
 use crate::{cpu::CPU, disassembly::Instruction, memory::Bus16};
 use std::collections::VecDeque;
 
+#[derive(Clone)]
 pub struct Debugger {
     pub states: VecDeque<ExecutionState>,
     pub backtrace_limit: usize,
@@ -30,7 +31,7 @@ impl Debugger {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ExecutionState {
     pub next_instruction: Instruction,
     pub a: u8,
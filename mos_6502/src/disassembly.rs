@@ -97,6 +97,7 @@ pub enum AddressingMode {
     Relative,
 }
 
+#[derive(Clone)]
 pub struct Instruction {
     pub opcode: u8,
     pub operand1: u8,
@@ -183,7 +184,7 @@ impl Instruction {
             0x09 => disassembly(Mnemonic::ORA, AddressingMode::Immediate, false),
             0x0A => disassembly(Mnemonic::ASL, AddressingMode::Accumulator, false),
             0x0B => disassembly(Mnemonic::ANC, AddressingMode::Immediate, true),
-            0x0C => disassembly(Mnemonic::NOP, AddressingMode::Implied, true),
+            0x0C => disassembly(Mnemonic::NOP, AddressingMode::Absolute, true),
             0x0D => disassembly(Mnemonic::ORA, AddressingMode::Absolute, false),
             0x0E => disassembly(Mnemonic::ASL, AddressingMode::Absolute, false),
             0x0F => disassembly(Mnemonic::SLO, AddressingMode::Absolute, true),
@@ -301,7 +302,7 @@ impl Instruction {
             0x79 => disassembly(Mnemonic::ADC, AddressingMode::AbsoluteY, false),
             0x7A => disassembly(Mnemonic::NOP, AddressingMode::Implied, true),
             0x7B => disassembly(Mnemonic::RRA, AddressingMode::AbsoluteY, true),
-            0x7C => disassembly(Mnemonic::NOP, AddressingMode::AbsoluteY, true),
+            0x7C => disassembly(Mnemonic::NOP, AddressingMode::AbsoluteX, true),
             0x7D => disassembly(Mnemonic::ADC, AddressingMode::AbsoluteX, false),
             0x7E => disassembly(Mnemonic::ROR, AddressingMode::AbsoluteX, false),
             0x7F => disassembly(Mnemonic::RRA, AddressingMode::AbsoluteX, true),
@@ -505,7 +506,7 @@ impl std::fmt::Display for Instruction {
             Immediate => format!("{} #${:02X}", mnemonic, operand1),
             Absolute => format!("{} ${:02X}{:02X}", mnemonic, operand2, operand1),
             AbsoluteX => format!("{} ${:02X}{:02X},X", mnemonic, operand2, operand1),
-            AbsoluteY => format!("{} ${:02X}{:02X},Y", mnemonic, operand2, operand2),
+            AbsoluteY => format!("{} ${:02X}{:02X},Y", mnemonic, operand2, operand1),
             Indirect => format!("{} (${:02X}{:02X})", mnemonic, operand2, operand1),
             IndirectX => format!("{} (${:02X},X)", mnemonic, operand1),
             IndirectY => format!("{} (${:02X}),Y", mnemonic, operand1),
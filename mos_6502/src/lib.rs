@@ -1,4 +1,5 @@
 pub mod cpu;
+pub mod cycle_analysis;
 pub mod debugging;
 pub mod disassembly;
 pub mod memory;
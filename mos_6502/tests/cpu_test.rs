@@ -1,5 +1,5 @@
 use mos_6502::{cpu::CPU, debugging::Debugger, memory::Bus16, memory::FlatMemory};
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn two_plus_two() {
@@ -32,8 +32,8 @@ fn klaus_functional_test_no_decimal() {
     let mut cpu = CPU::new();
     cpu.reset(&mut memory);
 
-    let debugger = Rc::new(RefCell::new(Debugger::new()));
-    cpu.attach_debugger(Rc::clone(&debugger));
+    let debugger = Arc::new(Mutex::new(Debugger::new()));
+    cpu.attach_debugger(Arc::clone(&debugger));
 
     let mut last_pc = cpu.pc;
     loop {
@@ -47,7 +47,7 @@ fn klaus_functional_test_no_decimal() {
     }
 
     if last_pc != 0x336D {
-        debugger.borrow().dump_backtrace();
+        debugger.lock().unwrap().dump_backtrace();
         panic!(
             "CPU trapped at PC={:X} in test={}",
             last_pc,
@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use nes::frame::Frame;
+use nes::palettes::Palette;
+use nes::post_processing::{
+    Crt, CrtParameters, Filter, FilterChain, Hq2x, Hq3x, Scale2x, Scale3x, Xbr2x,
+};
+
+/// A frame of diagonal stripes, so the edge-detecting filters take their slow paths.
+fn striped_frame() -> Frame {
+    let mut frame = Frame::new();
+    for y in 0..Frame::HEIGHT {
+        for x in 0..Frame::WIDTH {
+            frame.write(x, y, [0x0F, 0x16, 0x2A, 0x30][(x + y) / 3 % 4]);
+        }
+    }
+    frame
+}
+
+// At 60 fps each frame has 16.7 ms, which every filter here should fit in on one core.
+fn post_processing_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("post_processing");
+    group.sample_size(20);
+
+    let frame = striped_frame();
+    let palette = Palette::ntsc();
+
+    let mut bench = |name: &str, mut chain: FilterChain| {
+        group.bench_function(name, |b| {
+            b.iter(|| chain.apply(&frame, &palette).pixels().len())
+        });
+    };
+    bench("scale2x", FilterChain::new().then(Scale2x));
+    bench("scale3x", FilterChain::new().then(Scale3x));
+    bench("xbr2x", FilterChain::new().then(Xbr2x));
+    bench("hq2x", FilterChain::new().then(Hq2x));
+    bench("hq3x", FilterChain::new().then(Hq3x));
+    bench("crt", FilterChain::new().then(crt()));
+    bench("scale2x_crt", FilterChain::new().then(Scale2x).then(crt()));
+    group.finish();
+}
+
+fn crt() -> impl Filter {
+    Crt::new(CrtParameters::default())
+}
+
+criterion_group!(benches, post_processing_bench);
+criterion_main!(benches);
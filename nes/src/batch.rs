@@ -0,0 +1,166 @@
+use crate::nes::NES;
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+type Step = Arc<dyn Fn(&mut NES) + Send + Sync>;
+
+/// A set of consoles stepped in parallel by a pool of worker threads, one per core. The threads live as long as the
+/// batch, so stepping it every frame doesn't pay for spawning them again.
+pub struct Batch {
+    consoles: Vec<NES>,
+    workers: Vec<Worker>,
+}
+
+struct Worker {
+    jobs: Option<Sender<(Vec<NES>, Step)>>,
+    results: Receiver<Vec<NES>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Batch {
+    pub fn new(consoles: Vec<NES>) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let workers = (0..threads.min(consoles.len()).max(1))
+            .map(|_| Worker::spawn())
+            .collect();
+
+        Self { consoles, workers }
+    }
+
+    pub fn consoles(&self) -> &[NES] {
+        &self.consoles
+    }
+
+    pub fn consoles_mut(&mut self) -> &mut [NES] {
+        &mut self.consoles
+    }
+
+    pub fn into_consoles(mut self) -> Vec<NES> {
+        std::mem::take(&mut self.consoles)
+    }
+
+    /// Advances every console by one frame.
+    pub fn advance_to_next_frame(&mut self) {
+        self.for_each(|nes| {
+            nes.advance_to_next_frame();
+        });
+    }
+
+    /// Runs `step` on every console, handing one contiguous chunk to each worker. Each console is only ever touched by
+    /// one thread, so the results are the same as running them one after another.
+    pub fn for_each<F>(&mut self, step: F)
+    where
+        F: Fn(&mut NES) + Send + Sync + 'static,
+    {
+        let step: Step = Arc::new(step);
+        let chunk_size = self.consoles.len().div_ceil(self.workers.len()).max(1);
+
+        let mut busy = 0;
+        while !self.consoles.is_empty() {
+            let rest = self.consoles.split_off(chunk_size.min(self.consoles.len()));
+            let chunk = std::mem::replace(&mut self.consoles, rest);
+            self.workers[busy].send(chunk, Arc::clone(&step));
+            busy += 1;
+        }
+
+        for worker in &self.workers[..busy] {
+            self.consoles.extend(worker.receive());
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            // Closing the channel ends the worker's loop.
+            worker.jobs.take();
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn spawn() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<(Vec<NES>, Step)>();
+        let (result_sender, results) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for (mut chunk, step) in job_receiver {
+                chunk.iter_mut().for_each(|nes| step(nes));
+                if result_sender.send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, chunk: Vec<NES>, step: Step) {
+        self.jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send((chunk, step)).ok())
+            .expect("batch worker stopped");
+    }
+
+    fn receive(&self) -> Vec<NES> {
+        self.results.recv().expect("batch worker panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::StandardController, test_fixtures::nestest};
+
+    fn assert_clone_and_send<T: Clone + Send>() {}
+
+    #[test]
+    fn batches_match_sequential_runs() {
+        assert_clone_and_send::<NES>();
+
+        let mut nes = nestest();
+        nes.advance_to_next_frame();
+
+        // Branch off with different input on each console.
+        let consoles: Vec<NES> = (0..9)
+            .map(|i| {
+                let mut console = nes.clone();
+                let controller = StandardController {
+                    down: i % 2 == 1,
+                    ..Default::default()
+                };
+                console.update_controller_port_a(&controller);
+                console
+            })
+            .collect();
+        let mut sequential = consoles.clone();
+        let mut batch = Batch::new(consoles);
+
+        for _ in 0..5 {
+            batch.advance_to_next_frame();
+            for nes in &mut sequential {
+                nes.advance_to_next_frame();
+            }
+        }
+
+        let consoles = batch.into_consoles();
+        assert_eq!(consoles.len(), sequential.len());
+        for (batched, sequential) in consoles.iter().zip(&sequential) {
+            assert_eq!(batched.save_state(), sequential.save_state());
+        }
+        assert_ne!(consoles[0].save_state(), consoles[1].save_state());
+        // The original is untouched.
+        assert_eq!(nes.frame_count(), 1);
+    }
+}
@@ -1,15 +1,49 @@
 use crate::{
     memory::{Ram, Rom},
+    region::Region,
     rom::{Mirroring, RomFile, RomLoadError},
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
 };
 
-pub trait Cartridge {
+/// A game cartridge. Cartridges are `Send` so consoles can be handed to worker threads.
+pub trait Cartridge: Send {
     fn cpu_peek(&self, address: u16) -> u8;
     fn cpu_read(&mut self, address: u16) -> u8;
     fn cpu_write(&mut self, address: u16, value: u8);
 
+    fn ppu_peek(&self, address: u16) -> u8;
     fn ppu_read(&mut self, address: u16) -> u8;
     fn ppu_write(&mut self, address: u16, value: u8);
+
+    /// Whether the cartridge is asserting /IRQ, e.g. from a mapper's scanline counter.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// The console timing the game was made for, from its ROM header.
+    fn region(&self) -> Region {
+        Region::Ntsc
+    }
+
+    /// Clears what doesn't survive switching the console off: VRAM, CHR RAM and mapper registers. Battery-backed PRG RAM
+    /// keeps its contents.
+    fn power_cycle(&mut self) {}
+
+    /// Copies the cartridge, with all its current state, for cloning a running console.
+    fn box_clone(&self) -> Box<dyn Cartridge>;
+
+    /// Identifies the ROM, so a save state can't be loaded into a different game. See [`RomFile::hash`].
+    fn rom_hash(&self) -> u64;
+
+    /// Writes everything that can change while the game runs: bank registers, IRQ counters, VRAM and PRG/CHR RAM.
+    #[allow(unused_variables)]
+    fn save_state(&self, writer: &mut StateWriter) {}
+
+    /// Reads back what `save_state` wrote, in the same order.
+    #[allow(unused_variables)]
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        Ok(())
+    }
 }
 
 impl dyn Cartridge {
@@ -32,12 +66,19 @@ impl dyn Cartridge {
     }
 }
 
+impl Clone for Box<dyn Cartridge> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
 impl Default for Box<dyn Cartridge> {
     fn default() -> Self {
         Box::new(EmptyCartridgeSlot)
     }
 }
 
+#[derive(Clone)]
 pub struct EmptyCartridgeSlot;
 
 #[allow(unused_variables)]
@@ -54,6 +95,10 @@ impl Cartridge for EmptyCartridgeSlot {
         ()
     }
 
+    fn ppu_peek(&self, address: u16) -> u8 {
+        0
+    }
+
     fn ppu_read(&mut self, address: u16) -> u8 {
         0
     }
@@ -61,14 +106,26 @@ impl Cartridge for EmptyCartridgeSlot {
     fn ppu_write(&mut self, address: u16, value: u8) {
         ()
     }
+
+    fn rom_hash(&self) -> u64 {
+        // There's no ROM, so only states saved with the slot empty match.
+        0
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
 }
 
+#[derive(Clone)]
 struct NROM<const PRG_ROM_SIZE: usize> {
     vram: Ram<2048>,
     prg_rom: Rom<PRG_ROM_SIZE>,
     chr_rom: Rom<8192>,
     prg_ram: Option<Ram<2048>>,
     mirroring: Mirroring,
+    region: Region,
+    rom_hash: u64,
 }
 
 impl<const PRG_ROM_SIZE: usize> NROM<PRG_ROM_SIZE> {
@@ -85,6 +142,8 @@ impl<const PRG_ROM_SIZE: usize> NROM<PRG_ROM_SIZE> {
                 None
             },
             mirroring,
+            region: rom_file.header.region(),
+            rom_hash: rom_file.hash(),
         }
     }
 }
@@ -130,7 +189,7 @@ impl<const PRG_ROM_SIZE: usize> Cartridge for NROM<PRG_ROM_SIZE> {
         }
     }
 
-    fn ppu_read(&mut self, address: u16) -> u8 {
+    fn ppu_peek(&self, address: u16) -> u8 {
         match address {
             0..=0x1FFF => self.chr_rom[address],
             0x2000..=0x2FFF => {
@@ -141,6 +200,10 @@ impl<const PRG_ROM_SIZE: usize> Cartridge for NROM<PRG_ROM_SIZE> {
         }
     }
 
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.ppu_peek(address)
+    }
+
     fn ppu_write(&mut self, address: u16, value: u8) {
         match address {
             0..=0x1FFF => (), // Can't write to chr_rom.
@@ -151,6 +214,38 @@ impl<const PRG_ROM_SIZE: usize> Cartridge for NROM<PRG_ROM_SIZE> {
             _ => panic!("Cartridge: ppu bus addressed outside valid range!"),
         }
     }
+
+    fn region(&self) -> Region {
+        self.region
+    }
+
+    fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    fn power_cycle(&mut self) {
+        // NROM only has PRG RAM when it's battery-backed, so that's kept.
+        self.vram = Ram::new();
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.vram.save(writer);
+        if let Some(prg_ram) = &self.prg_ram {
+            prg_ram.save(writer);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.vram.load(reader)?;
+        if let Some(prg_ram) = &mut self.prg_ram {
+            prg_ram.load(reader)?;
+        }
+        Ok(())
+    }
 }
 
 fn mirror_vram_address(address: u16, mirroring: Mirroring) -> u16 {
@@ -0,0 +1,119 @@
+use crate::{
+    cartridge::Cartridge,
+    frame::Frame,
+    ppu::PPU,
+    region::Region,
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
+};
+use mos_6502::cycle_analysis::AccessCycles;
+
+/// Keeps the PPU in step with the CPU on a shared clock, counted in CPU cycles. Rather than stepping everything in
+/// lockstep, the PPU is only caught up when something could observe it: right before the CPU touches one of its
+/// registers, and at the end of each instruction. The APU and mapper timers belong here too once they exist.
+#[derive(Clone)]
+pub(crate) struct Clock {
+    /// The cycle the current instruction started on.
+    cycle: u64,
+    /// When the current instruction makes each of its bus accesses, relative to `cycle`.
+    access_cycles: AccessCycles,
+    /// Bus accesses made by the current instruction so far.
+    accesses: u64,
+    /// The cycle the PPU has been run up to.
+    ppu_cycle: u64,
+    /// PPU dots run so far. Outside NTSC and Dendy this isn't a whole multiple of `ppu_cycle`.
+    ppu_dots: u64,
+    /// Master clock cycles per CPU cycle and per PPU dot.
+    dividers: (u64, u64),
+    nmi_line: bool,
+    nmi_pending: bool,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            cycle: 0,
+            access_cycles: AccessCycles::default(),
+            accesses: 0,
+            ppu_cycle: 0,
+            ppu_dots: 0,
+            dividers: Region::Ntsc.clock_dividers(),
+            nmi_line: false,
+            nmi_pending: false,
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.dividers = region.clock_dividers();
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn start_instruction(&mut self, access_cycles: AccessCycles) {
+        self.access_cycles = access_cycles;
+        self.accesses = 0;
+    }
+
+    pub fn advance(&mut self, cycles: u64) {
+        self.cycle += cycles;
+    }
+
+    /// Returns the cycle the current instruction's next bus access happens on. Accesses are matched up with the
+    /// instruction's predicted timing in order; anything past that is assumed to take one cycle per access.
+    pub fn access(&mut self) -> u64 {
+        let offset = self
+            .access_cycles
+            .get(self.accesses)
+            .unwrap_or(self.accesses);
+        self.accesses += 1;
+        self.cycle + offset
+    }
+
+    /// Runs the PPU up to (but not including) the given cycle, watching its /NMI output for rising edges.
+    pub fn catch_up(
+        &mut self,
+        ppu: &mut PPU,
+        cartridge: &mut dyn Cartridge,
+        frame: &mut Frame,
+        cycle: u64,
+    ) {
+        let (cpu_divider, ppu_divider) = self.dividers;
+        while self.ppu_cycle < cycle {
+            self.ppu_cycle += 1;
+            let target_dots = self.ppu_cycle * cpu_divider / ppu_divider;
+            ppu.tick(cartridge, frame, target_dots - self.ppu_dots);
+            self.ppu_dots = target_dots;
+
+            let nmi_line = ppu.nmi_line();
+            self.nmi_pending |= nmi_line && !self.nmi_line;
+            self.nmi_line = nmi_line;
+        }
+    }
+
+    /// Takes the NMI edge latched so far. The CPU polls for it on the second-to-last cycle of each instruction.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+}
+
+/// Only the parts that carry over between instructions are saved: the access timing is set up afresh by
+/// `start_instruction`, and the dividers come from the region.
+impl Snapshot for Clock {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u64(self.cycle);
+        writer.write_u64(self.ppu_cycle);
+        writer.write_u64(self.ppu_dots);
+        writer.write_bool(self.nmi_line);
+        writer.write_bool(self.nmi_pending);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cycle = reader.read_u64()?;
+        self.ppu_cycle = reader.read_u64()?;
+        self.ppu_dots = reader.read_u64()?;
+        self.nmi_line = reader.read_bool()?;
+        self.nmi_pending = reader.read_bool()?;
+        Ok(())
+    }
+}
@@ -2,7 +2,9 @@ use mos_6502::memory::Bus16;
 
 use crate::{
     cartridge::Cartridge,
-    input::ControllerPort,
+    clock::Clock,
+    frame::Frame,
+    input::{ControllerPort, InputActivity, InputPoll},
     memory::Ram,
     ppu::{PpuRegister, PPU},
 };
@@ -39,24 +41,16 @@ fn map_address(address: u16) -> MappedAddress {
     }
 }
 
-fn read_page<B>(bus: &mut B, page: u8) -> [u8; 256]
-where
-    B: Bus16,
-{
-    let base_address = page as u16 * 256;
-    let mut page_data = [0; 256];
-    for i in 0..256 {
-        page_data[i] = bus.read_byte(base_address + i as u16);
-    }
-    page_data
-}
-
 pub(crate) struct CpuBus<'a> {
     pub ram: &'a mut Ram<2048>,
     pub ppu: &'a mut PPU,
     pub port_a: &'a mut ControllerPort,
     pub port_b: &'a mut ControllerPort,
     pub cartridge: &'a mut dyn Cartridge,
+    pub frame: &'a mut Frame,
+    pub clock: &'a mut Clock,
+    pub oam_dma_page: &'a mut Option<u8>,
+    pub input_activity: &'a mut InputActivity,
 }
 
 impl<'a> Bus16 for CpuBus<'a> {
@@ -73,31 +67,48 @@ impl<'a> Bus16 for CpuBus<'a> {
     }
 
     fn read_byte(&mut self, address: u16) -> u8 {
+        let cycle = self.clock.access();
         match map_address(address) {
             MappedAddress::Ram(address) => self.ram[address],
-            MappedAddress::Ppu(register) => self.ppu.read_register(self.cartridge, register),
+            MappedAddress::Ppu(register) => {
+                self.clock
+                    .catch_up(self.ppu, self.cartridge, self.frame, cycle);
+                self.ppu.read_register(self.cartridge, register)
+            }
             MappedAddress::OamDma => 0, // Open bus
-            MappedAddress::ControllerPortA => self.port_a.read(),
-            MappedAddress::ControllerPortB => self.port_b.read(),
+            MappedAddress::ControllerPortA => {
+                self.input_activity.read = true;
+                self.port_a.read()
+            }
+            MappedAddress::ControllerPortB => {
+                self.input_activity.read = true;
+                self.port_b.read()
+            }
             MappedAddress::Cartridge(address) => self.cartridge.cpu_read(address),
             MappedAddress::Unimplemented => 0,
         }
     }
 
     fn write_byte(&mut self, address: u16, value: u8) {
+        let cycle = self.clock.access();
         match map_address(address) {
             MappedAddress::Ram(address) => self.ram[address] = value,
             MappedAddress::Ppu(register) => {
+                self.clock
+                    .catch_up(self.ppu, self.cartridge, self.frame, cycle);
                 self.ppu.write_register(self.cartridge, register, value)
             }
-            MappedAddress::OamDma => {
-                let page_data = read_page(self, value);
-                self.ppu.oam_dma(&page_data);
-            }
+            // The transfer itself runs after the current instruction, see NES::run_oam_dma.
+            MappedAddress::OamDma => *self.oam_dma_page = Some(value),
             MappedAddress::ControllerPortA => {
                 if value & 0x01 != 0 {
                     self.port_a.poll();
                     self.port_b.poll();
+
+                    self.clock
+                        .catch_up(self.ppu, self.cartridge, self.frame, cycle);
+                    let (scanline, dot) = self.ppu.position();
+                    self.input_activity.polls.push(InputPoll { scanline, dot });
                 }
             }
             MappedAddress::ControllerPortB => (),
@@ -0,0 +1,304 @@
+use crate::{frame::Frame, input::StandardController, nes::NES, palettes::Palette};
+
+/// How an [`Env`] runs episodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvConfig {
+    /// Frames each action is held for.
+    pub frame_skip: u32,
+    /// After a reset, up to this many frames are run with no buttons held, the count chosen by the seeded random
+    /// number generator, so episodes don't all start on the same frame.
+    pub noop_max: u32,
+    pub seed: u64,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            frame_skip: 4,
+            noop_max: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// Works out the reward for a step from the RAM before and after it.
+pub type RewardFn = Box<dyn Fn(&[u8], &[u8]) -> f64 + Send>;
+/// Decides from the RAM whether an episode is over.
+pub type DoneFn = Box<dyn Fn(&[u8]) -> bool + Send>;
+
+/// A reinforcement learning environment in the style of Gym: reset to a starting point, then step with an action
+/// and get back an observation, a reward and whether the episode is over. Reward and done are worked out from the
+/// 2 KB of work RAM by functions the caller supplies, since only they know where a game keeps its score and lives.
+///
+/// Given the same seed and actions, every run is identical.
+pub struct Env {
+    nes: NES,
+    config: EnvConfig,
+    reset_point: NES,
+    reward: RewardFn,
+    done: DoneFn,
+    rng: SplitMix64,
+    previous_ram: Vec<u8>,
+    episode_done: bool,
+}
+
+/// What the agent sees: the frame and the work RAM.
+pub struct Observation<'a> {
+    pub frame: &'a Frame,
+    pub ram: &'a [u8],
+}
+
+pub struct Step<'a> {
+    pub observation: Observation<'a>,
+    pub reward: f64,
+    pub done: bool,
+}
+
+/// The PPU's view of the screen as data rather than pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuredObservation {
+    /// $2000-$2FFF: four nametables of 960 tile indices, each followed by its 64 byte attribute table. How many of
+    /// them are distinct depends on the cartridge's mirroring.
+    pub nametables: Vec<u8>,
+    /// 64 sprites of 4 bytes: Y, tile, attributes, X.
+    pub oam: Vec<u8>,
+    pub palette_ram: Vec<u8>,
+}
+
+impl Env {
+    /// Creates an environment whose episodes start from `nes` as it is now. There's no reward and episodes never end
+    /// until `set_reward` and `set_done` say otherwise.
+    pub fn new(nes: NES, config: EnvConfig) -> Self {
+        Self {
+            reset_point: nes.clone(),
+            previous_ram: nes.ram().to_vec(),
+            nes,
+            config,
+            reward: Box::new(|_, _| 0.0),
+            done: Box::new(|_| false),
+            rng: SplitMix64(config.seed),
+            episode_done: false,
+        }
+    }
+
+    /// Sets the reward function, which is given the RAM from before and after each step.
+    pub fn set_reward(&mut self, reward: impl Fn(&[u8], &[u8]) -> f64 + Send + 'static) {
+        self.reward = Box::new(reward);
+    }
+
+    /// Sets the predicate that ends an episode, checked against the RAM after every frame.
+    pub fn set_done(&mut self, done: impl Fn(&[u8]) -> bool + Send + 'static) {
+        self.done = Box::new(done);
+    }
+
+    /// Changes where `reset` goes back to, e.g. to a console that's had a save state loaded into it.
+    pub fn set_reset_point(&mut self, nes: NES) {
+        self.reset_point = nes;
+    }
+
+    /// Restarts the random number generator, which only picks the number of no-op frames after each reset.
+    pub fn seed(&mut self, seed: u64) {
+        self.config.seed = seed;
+        self.rng = SplitMix64(seed);
+    }
+
+    pub fn config(&self) -> EnvConfig {
+        self.config
+    }
+
+    pub fn nes(&self) -> &NES {
+        &self.nes
+    }
+
+    /// Goes back to the reset point and starts a new episode.
+    pub fn reset(&mut self) -> Observation<'_> {
+        self.nes = self.reset_point.clone();
+        let noops = match self.config.noop_max {
+            0 => 0,
+            noop_max => self.rng.next() % (noop_max as u64 + 1),
+        };
+        for _ in 0..noops {
+            self.nes
+                .update_controller_port_a(&StandardController::default());
+            self.nes.advance_to_next_frame();
+        }
+
+        self.previous_ram = self.nes.ram().to_vec();
+        self.episode_done = (self.done)(self.nes.ram());
+        self.observation()
+    }
+
+    /// Holds `action` on controller 1 for `frame_skip` frames, stopping early if the episode ends. Once it has
+    /// ended, stepping does nothing until the next reset.
+    pub fn step(&mut self, action: StandardController) -> Step<'_> {
+        if !self.episode_done {
+            for _ in 0..self.config.frame_skip.max(1) {
+                self.nes.update_controller_port_a(&action);
+                self.nes.advance_to_next_frame();
+                if (self.done)(self.nes.ram()) {
+                    self.episode_done = true;
+                    break;
+                }
+            }
+        }
+
+        let reward = (self.reward)(&self.previous_ram, self.nes.ram());
+        self.previous_ram.copy_from_slice(self.nes.ram());
+        Step {
+            reward,
+            done: self.episode_done,
+            observation: self.observation(),
+        }
+    }
+
+    pub fn observation(&self) -> Observation<'_> {
+        Observation {
+            frame: self.nes.borrow_frame(),
+            ram: self.nes.ram(),
+        }
+    }
+
+    /// The frame in 8-bit grayscale, shrunk by averaging `factor` by `factor` blocks of pixels. `factor` must divide
+    /// both 256 and 240, so it's one of 1, 2, 4, 8 or 16.
+    pub fn grayscale(&self, factor: usize) -> Vec<u8> {
+        assert!(
+            factor > 0
+                && Frame::WIDTH.is_multiple_of(factor)
+                && Frame::HEIGHT.is_multiple_of(factor),
+            "unsupported downsampling factor {factor}"
+        );
+
+        let palette = self.nes.palette();
+        let lumas: Vec<u32> = (0..Palette::SIZE as u16)
+            .map(|color_index| {
+                let (r, g, b) = palette.color(color_index);
+                (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+            })
+            .collect();
+
+        let (width, height) = (Frame::WIDTH / factor, Frame::HEIGHT / factor);
+        let mut sums = vec![0u32; width * height];
+        for (i, color_index) in self.nes.borrow_frame().color_indices().iter().enumerate() {
+            let (x, y) = (i % Frame::WIDTH / factor, i / Frame::WIDTH / factor);
+            sums[y * width + x] += lumas[*color_index as usize % Palette::SIZE];
+        }
+
+        let block = (factor * factor) as u32;
+        sums.into_iter().map(|sum| (sum / block) as u8).collect()
+    }
+
+    pub fn structured(&self) -> StructuredObservation {
+        StructuredObservation {
+            nametables: (0x2000..0x3000)
+                .map(|address| self.nes.peek_ppu_memory(address))
+                .collect(),
+            oam: self.nes.oam().to_vec(),
+            palette_ram: (0x3F00..0x3F20)
+                .map(|address| self.nes.peek_ppu_memory(address))
+                .collect(),
+        }
+    }
+}
+
+/// Sebastiano Vigna's SplitMix64: tiny, fast and plenty for picking no-op counts.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::nestest;
+
+    fn run_episode(env: &mut Env) -> (Vec<u8>, f64, usize) {
+        env.reset();
+        let mut total_reward = 0.0;
+        let mut steps = 0;
+        loop {
+            let action = StandardController {
+                down: steps % 4 == 0,
+                ..Default::default()
+            };
+            let step = env.step(action);
+            total_reward += step.reward;
+            steps += 1;
+            if step.done || steps == 20 {
+                return (step.observation.ram.to_vec(), total_reward, steps);
+            }
+        }
+    }
+
+    #[test]
+    fn episodes_are_deterministic() {
+        let config = EnvConfig {
+            frame_skip: 2,
+            noop_max: 10,
+            seed: 7,
+        };
+        let make_env = || {
+            let mut env = Env::new(nestest(), config);
+            // A reward for every byte of RAM that changed, and done once the byte at $D2 reaches 20.
+            env.set_reward(|before, after| {
+                before.iter().zip(after).filter(|(a, b)| a != b).count() as f64
+            });
+            env.set_done(|ram| ram[0xD2] >= 20);
+            env
+        };
+
+        let mut env = make_env();
+        let first = run_episode(&mut env);
+        let second = run_episode(&mut env);
+        // Different seeds start episodes on different frames.
+        let start_frames: Vec<u64> = (0..8)
+            .map(|seed| {
+                let mut env = make_env();
+                env.seed(seed);
+                env.reset();
+                env.nes().frame_count()
+            })
+            .collect();
+        assert!(start_frames.iter().any(|frame| *frame != start_frames[0]));
+
+        let mut other = make_env();
+        assert_eq!(run_episode(&mut other), first);
+        assert_eq!(run_episode(&mut other), second);
+
+        // Stepping after the episode ends changes nothing.
+        let mut env = make_env();
+        env.set_done(|_| true);
+        env.reset();
+        let frame_count = env.nes().frame_count();
+        assert!(env.step(StandardController::default()).done);
+        assert_eq!(env.nes().frame_count(), frame_count);
+    }
+
+    #[test]
+    fn observations() {
+        let mut env = Env::new(nestest(), EnvConfig::default());
+        env.reset();
+        for _ in 0..5 {
+            env.step(StandardController::default());
+        }
+
+        let gray = env.grayscale(2);
+        assert_eq!(gray.len(), 128 * 120);
+        assert_eq!(env.grayscale(1).len(), Frame::WIDTH * Frame::HEIGHT);
+
+        let structured = env.structured();
+        assert_eq!(structured.nametables.len(), 4096);
+        assert_eq!(structured.oam.len(), 256);
+        assert_eq!(structured.palette_ram.len(), 32);
+        // nestest draws its menu as text, so the first nametable isn't blank.
+        assert!(structured.nametables[..960]
+            .iter()
+            .any(|tile| *tile != 0x20 && *tile != 0));
+    }
+}
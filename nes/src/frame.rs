@@ -1,34 +1,194 @@
+use crate::{
+    palettes::Palette,
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
+};
+use std::sync::OnceLock;
+
+/// A frame of PPU output, stored as the 9-bit color of each pixel (3 emphasis bits above the 6-bit palette RAM value).
+/// Converting to RGB happens once per frame, in whatever pixel format the front end wants.
+#[derive(Clone)]
 pub struct Frame {
-    data: Vec<u8>,
+    color_indices: Vec<u16>,
+    phase: u8,
+    /// The frame as RGB888 for the deprecated `NES::data_rgb8`, converted at most once per frame.
+    rgb8: OnceLock<Vec<u8>>,
 }
 
 impl Frame {
     pub const WIDTH: usize = 256;
     pub const HEIGHT: usize = 240;
+    #[deprecated(
+        note = "frames can be converted to several pixel formats; use `PixelFormat::bytes_per_pixel`"
+    )]
     pub const BYTES_PER_PIXEL: usize = 3;
 
     pub fn new() -> Self {
         Self {
-            data: vec![0; Frame::WIDTH * Frame::HEIGHT * Frame::BYTES_PER_PIXEL],
+            color_indices: vec![0x0F; Frame::WIDTH * Frame::HEIGHT],
+            phase: 0,
+            rgb8: OnceLock::new(),
         }
     }
 
-    pub fn write(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
-        let base_idx = (y * Frame::WIDTH + x) * Frame::BYTES_PER_PIXEL;
-        self.data[base_idx + 0] = rgb.0;
-        self.data[base_idx + 1] = rgb.1;
-        self.data[base_idx + 2] = rgb.2;
+    pub fn write(&mut self, x: usize, y: usize, color_index: u16) {
+        self.color_indices[y * Frame::WIDTH + x] = color_index;
+    }
+
+    /// The frame as RGB888 through `palette`, converted the first time it's asked for since the frame started.
+    pub(crate) fn rgb8(&self, palette: &Palette) -> &[u8] {
+        self.rgb8.get_or_init(|| {
+            let mut rgb8 = vec![0; self.color_indices.len() * 3];
+            self.convert(palette, PixelFormat::Rgb888, &mut rgb8);
+            rgb8
+        })
+    }
+
+    /// Drops the RGB888 copy, e.g. because the palette changed.
+    pub(crate) fn discard_rgb8(&mut self) {
+        self.rgb8.take();
     }
 
-    pub fn data_rgb8(&self) -> &[u8] {
-        &self.data
+    /// The 9-bit PPU color of every pixel, row by row.
+    pub fn color_indices(&self) -> &[u16] {
+        &self.color_indices
     }
 
-    pub fn clear_with(&mut self, rgb: (u8, u8, u8)) {
-        for base_idx in (0..self.data.len()).step_by(3) {
-            self.data[base_idx + 0] = rgb.0;
-            self.data[base_idx + 1] = rgb.1;
-            self.data[base_idx + 2] = rgb.2;
+    /// Where the color subcarrier was, out of 12 phases, at the first pixel of the frame. It moves from frame to
+    /// frame, which is what makes NTSC artifacts crawl.
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    /// Called as each frame starts, which also drops the previous frame's RGB888 copy.
+    pub fn set_phase(&mut self, phase: u8) {
+        self.phase = phase;
+        self.rgb8.take();
+    }
+
+    /// Converts the whole frame to `format` through `palette` in a single pass. `output` must hold exactly
+    /// `WIDTH * HEIGHT * format.bytes_per_pixel()` bytes.
+    pub fn convert(&self, palette: &Palette, format: PixelFormat, output: &mut [u8]) {
+        let bytes_per_pixel = format.bytes_per_pixel();
+        assert_eq!(output.len(), self.color_indices.len() * bytes_per_pixel);
+
+        let lookup = palette.encoded(format);
+        for (pixel, color_index) in output
+            .chunks_exact_mut(bytes_per_pixel)
+            .zip(&self.color_indices)
+        {
+            pixel
+                .copy_from_slice(&lookup[*color_index as usize % Palette::SIZE][..bytes_per_pixel]);
         }
     }
 }
+
+impl Snapshot for Frame {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.phase);
+        for color_index in &self.color_indices {
+            writer.write_u16(*color_index);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.phase = reader.read_u8()?;
+        for color_index in self.color_indices.iter_mut() {
+            *color_index = reader.read_u16()?;
+        }
+        self.rgb8.take();
+        Ok(())
+    }
+}
+
+/// Pixel layouts a frame can be converted to, named by their byte order in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb888,
+    Rgba8888,
+    Bgra8888,
+    /// 16 bits per pixel, stored little-endian.
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub(crate) const COUNT: usize = 4;
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => 4,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+
+    pub(crate) fn encode(&self, (r, g, b): (u8, u8, u8)) -> [u8; 4] {
+        match self {
+            PixelFormat::Rgb888 => [r, g, b, 0],
+            PixelFormat::Rgba8888 => [r, g, b, 0xFF],
+            PixelFormat::Bgra8888 => [b, g, r, 0xFF],
+            PixelFormat::Rgb565 => {
+                let [low, high] =
+                    ((r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3).to_le_bytes();
+                [low, high, 0, 0]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_formats() {
+        let palette = Palette::rgb_2c03();
+        let mut frame = Frame::new();
+        // 2C03 color $16 is pure red.
+        frame.write(1, 0, 0x16);
+
+        let convert = |format: PixelFormat| {
+            let mut output = vec![0; Frame::WIDTH * Frame::HEIGHT * format.bytes_per_pixel()];
+            frame.convert(&palette, format, &mut output);
+            output
+        };
+
+        assert_eq!(convert(PixelFormat::Rgb888)[3..6], [0xFF, 0x00, 0x00]);
+        assert_eq!(
+            convert(PixelFormat::Rgba8888)[4..8],
+            [0xFF, 0x00, 0x00, 0xFF]
+        );
+        assert_eq!(
+            convert(PixelFormat::Bgra8888)[4..8],
+            [0x00, 0x00, 0xFF, 0xFF]
+        );
+        assert_eq!(convert(PixelFormat::Rgb565)[2..4], 0xF800u16.to_le_bytes());
+        // Everything else is $0F, black.
+        assert_eq!(
+            convert(PixelFormat::Rgba8888)[0..4],
+            [0x00, 0x00, 0x00, 0xFF]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn rgb8_data_is_converted_once_per_frame() {
+        let palette = Palette::rgb_2c03();
+        let mut frame = Frame::new();
+        frame.write(1, 0, 0x16);
+        assert_eq!(
+            frame.rgb8(&palette).len(),
+            Frame::WIDTH * Frame::HEIGHT * Frame::BYTES_PER_PIXEL
+        );
+        assert_eq!(frame.rgb8(&palette)[3..6], [0xFF, 0x00, 0x00]);
+
+        // Writes during a frame don't touch the copy; the next frame does.
+        frame.write(1, 0, 0x30);
+        assert_eq!(frame.rgb8(&palette)[3..6], [0xFF, 0x00, 0x00]);
+        frame.set_phase(4);
+        assert_eq!(frame.rgb8(&palette)[3..6], [0xFF, 0xFF, 0xFF]);
+
+        let gray = Palette::from_pal_file(&[0x12; 192]).unwrap();
+        frame.discard_rgb8();
+        assert_eq!(frame.rgb8(&gray)[3..6], [0x12, 0x12, 0x12]);
+    }
+}
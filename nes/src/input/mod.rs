@@ -1,3 +1,6 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+#[derive(Clone)]
 pub(crate) struct ControllerPort {
     read_buffer: Vec<u8>,
     index: u8,
@@ -47,10 +50,61 @@ impl Default for ControllerPort {
     }
 }
 
+/// When in the frame the game strobed $4016 to latch the controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputPoll {
+    pub scanline: u16,
+    pub dot: u16,
+}
+
+/// What the game did with the controllers during a frame.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputActivity {
+    pub polls: Vec<InputPoll>,
+    /// Whether either port was read.
+    pub read: bool,
+}
+
 pub trait ControllerState {
     fn read_buffer(&self) -> Vec<u8>;
     fn overrun_default(&self) -> u8;
 }
 
+impl Snapshot for ControllerPort {
+    fn save(&self, writer: &mut StateWriter) {
+        save_read_buffer(writer, &self.read_buffer, self.overrun_default);
+        writer.write_u8(self.index);
+        match &self.incoming_state {
+            Some((read_buffer, overrun_default)) => {
+                writer.write_bool(true);
+                save_read_buffer(writer, read_buffer, *overrun_default);
+            }
+            None => writer.write_bool(false),
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        (self.read_buffer, self.overrun_default) = load_read_buffer(reader)?;
+        self.index = reader.read_u8()?;
+        self.incoming_state = match reader.read_bool()? {
+            true => Some(load_read_buffer(reader)?),
+            false => None,
+        };
+        Ok(())
+    }
+}
+
+fn save_read_buffer(writer: &mut StateWriter, read_buffer: &[u8], overrun_default: u8) {
+    writer.write_u8(read_buffer.len() as u8);
+    writer.write_bytes(read_buffer);
+    writer.write_u8(overrun_default);
+}
+
+fn load_read_buffer(reader: &mut StateReader) -> Result<(Vec<u8>, u8), SaveStateError> {
+    let mut read_buffer = vec![0; reader.read_u8()? as usize];
+    reader.read_bytes(&mut read_buffer)?;
+    Ok((read_buffer, reader.read_u8()?))
+}
+
 mod standard_controller;
 pub use standard_controller::StandardController;
@@ -1,6 +1,6 @@
 use super::ControllerState;
 
-#[derive(Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct StandardController {
     pub a: bool,
     pub b: bool,
@@ -0,0 +1,100 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+/// Everything on the console that can pull the CPU's /IRQ line low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqSource {
+    ApuFrameCounter,
+    Dmc,
+    Cartridge,
+    Expansion,
+}
+
+impl IrqSource {
+    pub const ALL: [IrqSource; 4] = [
+        IrqSource::ApuFrameCounter,
+        IrqSource::Dmc,
+        IrqSource::Cartridge,
+        IrqSource::Expansion,
+    ];
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The CPU's /IRQ input. Each source holds its own flag until it's acknowledged, and the CPU sees the wired-OR of all
+/// of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IrqLine(u8);
+
+impl IrqLine {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn assert(&mut self, source: IrqSource) {
+        self.0 |= source.mask();
+    }
+
+    pub fn acknowledge(&mut self, source: IrqSource) {
+        self.0 &= !source.mask();
+    }
+
+    pub fn set(&mut self, source: IrqSource, asserted: bool) {
+        match asserted {
+            true => self.assert(source),
+            false => self.acknowledge(source),
+        }
+    }
+
+    pub fn is_asserted_by(&self, source: IrqSource) -> bool {
+        self.0 & source.mask() != 0
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.0 != 0
+    }
+
+    /// The sources currently asserting the line.
+    pub fn sources(&self) -> impl Iterator<Item = IrqSource> + '_ {
+        IrqSource::ALL
+            .into_iter()
+            .filter(|source| self.is_asserted_by(*source))
+    }
+}
+
+impl Snapshot for IrqLine {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0 = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wired_or() {
+        let mut line = IrqLine::new();
+        assert!(!line.is_asserted());
+
+        line.assert(IrqSource::Cartridge);
+        line.assert(IrqSource::Dmc);
+        assert!(line.is_asserted());
+        assert_eq!(
+            line.sources().collect::<Vec<_>>(),
+            [IrqSource::Dmc, IrqSource::Cartridge]
+        );
+
+        // The line stays low until every source has been acknowledged.
+        line.acknowledge(IrqSource::Cartridge);
+        assert!(line.is_asserted());
+        line.acknowledge(IrqSource::Dmc);
+        assert!(!line.is_asserted());
+    }
+}
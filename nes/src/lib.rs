@@ -1,8 +1,21 @@
+pub mod batch;
 pub mod cartridge;
+mod clock;
 mod cpu_bus;
+pub mod env;
 pub mod frame;
 pub mod input;
+pub mod interrupts;
 mod memory;
+pub mod movie;
 pub mod nes;
+pub mod ntsc_filter;
+pub mod post_processing;
 mod ppu;
+pub use ppu::palettes;
+pub mod region;
+pub mod rewind;
 pub mod rom;
+pub mod save_state;
+#[cfg(test)]
+mod test_fixtures;
@@ -1,5 +1,7 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
 use std::ops::{Index, IndexMut};
 
+#[derive(Clone)]
 pub struct Ram<const SIZE: usize> {
     bytes: [u8; SIZE],
 }
@@ -9,15 +11,21 @@ impl<const SIZE: usize> Ram<SIZE> {
         Self { bytes: [0; SIZE] }
     }
 
-    pub fn copy_from_slice(&mut self, slice: &[u8]) {
-        self.bytes.copy_from_slice(slice)
-    }
-
     pub fn as_slice(&self) -> &[u8] {
         &self.bytes
     }
 }
 
+impl<const SIZE: usize> Snapshot for Ram<SIZE> {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.bytes);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        reader.read_bytes(&mut self.bytes)
+    }
+}
+
 impl<const SIZE: usize> Index<u16> for Ram<SIZE> {
     type Output = u8;
 
@@ -32,6 +40,7 @@ impl<const SIZE: usize> IndexMut<u16> for Ram<SIZE> {
     }
 }
 
+#[derive(Clone)]
 pub struct Rom<const SIZE: usize> {
     bytes: [u8; SIZE],
 }
@@ -52,6 +61,7 @@ impl<const SIZE: usize> Index<u16> for Rom<SIZE> {
     }
 }
 
+#[derive(Clone)]
 pub struct PaletteRam {
     bytes: [u8; Self::SIZE],
 }
@@ -74,6 +84,16 @@ impl PaletteRam {
     }
 }
 
+impl Snapshot for PaletteRam {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.bytes);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        reader.read_bytes(&mut self.bytes)
+    }
+}
+
 impl Index<u16> for PaletteRam {
     type Output = u8;
 
@@ -0,0 +1,314 @@
+//! BizHawk's movie format: a zip archive holding, among other things, Header.txt ("key value" lines) and
+//! Input Log.txt. The input log names its buttons in a `LogKey:` line, grouped by '#', and then has a line per
+//! frame with a '|'-separated field per group and a character per button, '.' when it isn't pressed.
+
+use super::{
+    format_checkpoint, parse_checkpoint, zip, ConsoleEvent, Movie, MovieError, MovieFrame,
+    MovieStart,
+};
+use crate::{input::StandardController, region::Region};
+
+const HEADER: &str = "Header.txt";
+const INPUT_LOG: &str = "Input Log.txt";
+const COMMENTS: &str = "Comments.txt";
+
+/// NesHawk's buttons for two standard controllers, with the mnemonics it logs them as.
+const LOG_KEY: [&[(&str, char)]; 3] = [
+    &[("Reset", 'r'), ("Power", 'P')],
+    &[
+        ("P1 Up", 'U'),
+        ("P1 Down", 'D'),
+        ("P1 Left", 'L'),
+        ("P1 Right", 'R'),
+        ("P1 Start", 'S'),
+        ("P1 Select", 's'),
+        ("P1 B", 'B'),
+        ("P1 A", 'A'),
+    ],
+    &[
+        ("P2 Up", 'U'),
+        ("P2 Down", 'D'),
+        ("P2 Left", 'L'),
+        ("P2 Right", 'R'),
+        ("P2 Start", 'S'),
+        ("P2 Select", 's'),
+        ("P2 B", 'B'),
+        ("P2 A", 'A'),
+    ],
+];
+
+/// Presses the named button in `frame`, returning false if it isn't one this emulator has.
+fn press(frame: &mut MovieFrame, button: &str) -> bool {
+    let (controller, name) = match button.split_once(' ') {
+        Some(("P1", name)) => (&mut frame.port_a, name),
+        Some(("P2", name)) => (&mut frame.port_b, name),
+        _ => {
+            match button {
+                "Reset" if frame.event.is_none() => frame.event = Some(ConsoleEvent::SoftReset),
+                "Reset" => {}
+                "Power" => frame.event = Some(ConsoleEvent::Power),
+                _ => return false,
+            }
+            return true;
+        }
+    };
+    let held = match name {
+        "Up" => &mut controller.up,
+        "Down" => &mut controller.down,
+        "Left" => &mut controller.left,
+        "Right" => &mut controller.right,
+        "Start" => &mut controller.start,
+        "Select" => &mut controller.select,
+        "B" => &mut controller.b,
+        "A" => &mut controller.a,
+        _ => return false,
+    };
+    *held = true;
+    true
+}
+
+fn is_pressed(frame: &MovieFrame, button: &str) -> bool {
+    let controller: &StandardController = match button.split_once(' ') {
+        Some(("P1", _)) => &frame.port_a,
+        Some(("P2", _)) => &frame.port_b,
+        _ => {
+            return match button {
+                "Reset" => frame.event == Some(ConsoleEvent::SoftReset),
+                _ => frame.event == Some(ConsoleEvent::Power),
+            }
+        }
+    };
+    match button.split_once(' ').unwrap().1 {
+        "Up" => controller.up,
+        "Down" => controller.down,
+        "Left" => controller.left,
+        "Right" => controller.right,
+        "Start" => controller.start,
+        "Select" => controller.select,
+        "B" => controller.b,
+        _ => controller.a,
+    }
+}
+
+impl Movie {
+    /// Reads a .bk2 movie recorded with the NES cores. Movies starting from a save state can't be read, since
+    /// BizHawk's save states aren't compatible with this emulator's; nor can ones using buttons other than the two
+    /// standard controllers, reset and power.
+    pub fn from_bk2(bytes: &[u8]) -> Result<Movie, MovieError> {
+        let files = zip::read(bytes)?;
+        let file = |name: &str| {
+            files
+                .iter()
+                .find(|(file_name, _)| file_name == name)
+                .map(|(_, contents)| String::from_utf8_lossy(contents).into_owned())
+        };
+
+        let mut movie = Movie::new(MovieStart::PowerOn, Region::Ntsc);
+        for line in file(HEADER).unwrap_or_default().lines() {
+            let (key, value) = line.trim_end().split_once(' ').unwrap_or((line, ""));
+            match key {
+                "Platform" if value != "NES" => {
+                    return Err(MovieError::Unsupported(format!("{value} movies")))
+                }
+                "StartsFromSavestate" | "StartsFromSaveRam" if value == "True" => {
+                    return Err(MovieError::Unsupported(
+                        "movies starting from BizHawk save states or SaveRAM".to_string(),
+                    ))
+                }
+                "PAL" if value == "True" => movie.region = Region::Pal,
+                "GameName" => movie.rom_filename = value.to_string(),
+                "rerecordCount" => movie.rerecord_count = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        for line in file(COMMENTS).unwrap_or_default().lines() {
+            movie.checkpoints.extend(parse_checkpoint(line));
+        }
+
+        let input_log =
+            file(INPUT_LOG).ok_or_else(|| MovieError::Malformed(format!("no {INPUT_LOG}")))?;
+        let mut log_key: Vec<Vec<String>> = LOG_KEY
+            .iter()
+            .map(|group| group.iter().map(|(name, _)| name.to_string()).collect())
+            .collect();
+        for line in input_log.lines().map(|line| line.trim_end()) {
+            if let Some(key) = line.strip_prefix("LogKey:") {
+                log_key = key
+                    .split('#')
+                    .filter(|group| !group.is_empty())
+                    .map(|group| {
+                        group
+                            .split('|')
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .collect();
+            } else if let Some(fields) = line.strip_prefix('|') {
+                movie.frames.push(parse_frame(&log_key, fields)?);
+            }
+        }
+        Ok(movie)
+    }
+
+    /// Writes the movie as .bk2, with the entries stored uncompressed. Checkpoints go in Comments.txt.
+    pub fn to_bk2(&self) -> Result<Vec<u8>, MovieError> {
+        if self.start != MovieStart::PowerOn {
+            return Err(MovieError::Unsupported(
+                "BK2 movies starting from a save state".to_string(),
+            ));
+        }
+        if self.region == Region::Dendy {
+            return Err(MovieError::Unsupported(
+                "Dendy timing in a BK2 movie".to_string(),
+            ));
+        }
+
+        let header = format!(
+            "MovieVersion BizHawk v2.0.0\nPlatform NES\nGameName {}\nrerecordCount {}\nPAL {}\nCore NesHawk\n",
+            self.rom_filename,
+            self.rerecord_count,
+            if self.region == Region::Pal { "True" } else { "False" }
+        );
+
+        let mut input_log = String::from("[Input]\nLogKey:");
+        for group in LOG_KEY {
+            input_log.push('#');
+            for (name, _) in group {
+                input_log += &format!("{name}|");
+            }
+        }
+        input_log.push('\n');
+        for frame in &self.frames {
+            input_log.push('|');
+            for group in LOG_KEY {
+                for (name, mnemonic) in group {
+                    input_log.push(if is_pressed(frame, name) {
+                        *mnemonic
+                    } else {
+                        '.'
+                    });
+                }
+                input_log.push('|');
+            }
+            input_log.push('\n');
+        }
+        input_log += "[/Input]\n";
+
+        let comments: String = self
+            .checkpoints
+            .iter()
+            .map(|checkpoint| format_checkpoint(checkpoint) + "\n")
+            .collect();
+
+        Ok(zip::write(&[
+            (HEADER, header.as_bytes()),
+            (INPUT_LOG, input_log.as_bytes()),
+            (COMMENTS, comments.as_bytes()),
+        ]))
+    }
+}
+
+fn parse_frame(log_key: &[Vec<String>], fields: &str) -> Result<MovieFrame, MovieError> {
+    let mut frame = MovieFrame::default();
+    let fields: Vec<&str> = fields.split('|').collect();
+    for (group, field) in log_key.iter().zip(&fields) {
+        if field.chars().count() != group.len() {
+            return Err(MovieError::Malformed(format!("bad input field {field:?}")));
+        }
+        for (button, state) in group.iter().zip(field.chars()) {
+            if state != '.' && state != ' ' && !press(&mut frame, button) {
+                return Err(MovieError::Unsupported(format!("the {button} button")));
+            }
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movie::Checkpoint;
+
+    fn archive(input_log: &str) -> Vec<u8> {
+        zip::write(&[
+            (
+                HEADER,
+                b"MovieVersion BizHawk v2.0.0\nPlatform NES\nGameName Some Game\nrerecordCount 3\n",
+            ),
+            (INPUT_LOG, input_log.as_bytes()),
+        ])
+    }
+
+    #[test]
+    fn reads_bizhawk_movies() {
+        let movie = Movie::from_bk2(&archive(
+            "[Input]\nLogKey:#Reset|Power|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|\
+             #P2 Up|P2 Down|P2 Left|P2 Right|P2 Start|P2 Select|P2 B|P2 A|\n\
+             |..|........|........|\n|r.|U.....BA|....S...|\n|.P|........|........|\n[/Input]\n",
+        ))
+        .unwrap();
+        assert_eq!(movie.rom_filename, "Some Game");
+        assert_eq!(movie.rerecord_count, 3);
+        assert_eq!(movie.frames.len(), 3);
+        assert_eq!(
+            movie.frames[1],
+            MovieFrame {
+                event: Some(ConsoleEvent::SoftReset),
+                port_a: StandardController {
+                    up: true,
+                    b: true,
+                    a: true,
+                    ..Default::default()
+                },
+                port_b: StandardController {
+                    start: true,
+                    ..Default::default()
+                },
+            }
+        );
+        assert_eq!(movie.frames[2].event, Some(ConsoleEvent::Power));
+
+        // The log key decides which character is which button.
+        let reordered =
+            Movie::from_bk2(&archive("LogKey:#P1 A|P1 B|#Power|\n|A.|.|\n|.B|P|\n")).unwrap();
+        assert!(reordered.frames[0].port_a.a);
+        assert!(reordered.frames[1].port_a.b);
+        assert_eq!(reordered.frames[1].event, Some(ConsoleEvent::Power));
+
+        assert!(matches!(
+            Movie::from_bk2(&archive("LogKey:#P3 A|\n|A|\n")),
+            Err(MovieError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut movie = Movie::new(MovieStart::PowerOn, Region::Pal);
+        movie.rom_filename = "Some Game".to_string();
+        movie.rerecord_count = 7;
+        movie.checkpoints.push(Checkpoint {
+            frame: 1,
+            ram_hash: 0x0123_4567_89AB_CDEF,
+        });
+        for i in 0..20 {
+            movie.frames.push(MovieFrame {
+                event: [
+                    None,
+                    Some(ConsoleEvent::SoftReset),
+                    Some(ConsoleEvent::Power),
+                ][i % 3],
+                port_a: StandardController {
+                    right: i % 2 == 0,
+                    select: i % 5 == 0,
+                    ..Default::default()
+                },
+                port_b: StandardController {
+                    down: i % 7 == 0,
+                    ..Default::default()
+                },
+            });
+        }
+        assert_eq!(Movie::from_bk2(&movie.to_bk2().unwrap()).unwrap(), movie);
+    }
+}
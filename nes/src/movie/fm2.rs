@@ -0,0 +1,228 @@
+//! FCEUX's text movie format. A header of "key value" lines is followed by a line per frame:
+//! `|commands|port 0|port 1|port 2|`, where each gamepad is eight characters in the order RLDUTSBA and any
+//! character other than a space or '.' means the button is held.
+
+use super::{
+    format_checkpoint, parse_checkpoint, ConsoleEvent, Movie, MovieError, MovieFrame, MovieStart,
+};
+use crate::{input::StandardController, region::Region};
+
+const SOFT_RESET: u32 = 1;
+const POWER: u32 = 2;
+const BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+fn parse_gamepad(field: &str) -> Result<StandardController, MovieError> {
+    if field.is_empty() {
+        return Ok(StandardController::default());
+    }
+    let held: Vec<bool> = field.chars().map(|c| c != ' ' && c != '.').collect();
+    let [right, left, down, up, start, select, b, a] = held[..] else {
+        return Err(MovieError::Malformed(format!(
+            "bad gamepad input {field:?}"
+        )));
+    };
+    Ok(StandardController {
+        a,
+        b,
+        select,
+        start,
+        up,
+        down,
+        left,
+        right,
+    })
+}
+
+fn format_gamepad(controller: &StandardController) -> String {
+    let held = [
+        controller.right,
+        controller.left,
+        controller.down,
+        controller.up,
+        controller.start,
+        controller.select,
+        controller.b,
+        controller.a,
+    ];
+    held.iter()
+        .zip(BUTTONS)
+        .map(|(held, button)| if *held { *button as char } else { '.' })
+        .collect()
+}
+
+impl Movie {
+    /// Reads an .fm2 movie. Only text movies of standard gamepads starting from power-on can be read; FCEUX's save
+    /// states aren't compatible with this emulator's.
+    pub fn from_fm2(text: &str) -> Result<Movie, MovieError> {
+        let mut movie = Movie::new(MovieStart::PowerOn, Region::Ntsc);
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.starts_with('|') {
+                movie.frames.push(parse_frame(line)?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match (key, value) {
+                ("palFlag", "1") => movie.region = Region::Pal,
+                ("romFilename", name) => movie.rom_filename = name.to_string(),
+                ("rerecordCount", count) => movie.rerecord_count = count.parse().unwrap_or(0),
+                ("comment", comment) => movie.checkpoints.extend(parse_checkpoint(comment)),
+                ("savestate", _) => {
+                    return Err(MovieError::Unsupported(
+                        "movies starting from an FCEUX save state".to_string(),
+                    ))
+                }
+                ("binary", "1") => {
+                    return Err(MovieError::Unsupported("binary FM2 input".to_string()))
+                }
+                ("fourscore", "1") => {
+                    return Err(MovieError::Unsupported("the Four Score".to_string()))
+                }
+                ("port0" | "port1", "0" | "1") | ("port2", "0") => {}
+                ("port0" | "port1" | "port2", device) => {
+                    return Err(MovieError::Unsupported(format!(
+                        "input device {device} in {key}"
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(movie)
+    }
+
+    /// Writes the movie as .fm2. There's no ROM checksum, so FCEUX will warn that it can't confirm the ROM before
+    /// playing. Checkpoints go in comment lines.
+    pub fn to_fm2(&self) -> Result<String, MovieError> {
+        if self.start != MovieStart::PowerOn {
+            return Err(MovieError::Unsupported(
+                "FM2 movies starting from a save state".to_string(),
+            ));
+        }
+        let pal_flag = match self.region {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => {
+                return Err(MovieError::Unsupported(
+                    "Dendy timing in an FM2 movie".to_string(),
+                ))
+            }
+        };
+
+        let mut text = format!(
+            "version 3\nemuVersion 22020\nrerecordCount {}\npalFlag {pal_flag}\nromFilename {}\n\
+             guid 00000000-0000-0000-0000-000000000000\nfourscore 0\nmicrophone 0\nport0 1\nport1 1\nport2 0\n\
+             FDS 0\nNewPPU 0\n",
+            self.rerecord_count, self.rom_filename
+        );
+        for checkpoint in &self.checkpoints {
+            text += &format!("comment {}\n", format_checkpoint(checkpoint));
+        }
+        for frame in &self.frames {
+            let commands = match frame.event {
+                None => 0,
+                Some(ConsoleEvent::SoftReset) => SOFT_RESET,
+                Some(ConsoleEvent::Power) => POWER,
+            };
+            text += &format!(
+                "|{commands}|{}|{}||\n",
+                format_gamepad(&frame.port_a),
+                format_gamepad(&frame.port_b)
+            );
+        }
+        Ok(text)
+    }
+}
+
+fn parse_frame(line: &str) -> Result<MovieFrame, MovieError> {
+    let fields: Vec<&str> = line.split('|').collect();
+    if fields.len() < 5 {
+        return Err(MovieError::Malformed(format!("bad input line {line:?}")));
+    }
+
+    let commands: u32 = fields[1]
+        .trim()
+        .parse()
+        .map_err(|_| MovieError::Malformed(format!("bad commands in {line:?}")))?;
+    let event = match commands {
+        0 => None,
+        SOFT_RESET => Some(ConsoleEvent::SoftReset),
+        // A power cycle makes a soft reset alongside it moot.
+        POWER | 3 => Some(ConsoleEvent::Power),
+        _ => {
+            return Err(MovieError::Unsupported(format!(
+                "FM2 command flags {commands:#x}"
+            )))
+        }
+    };
+    Ok(MovieFrame {
+        event,
+        port_a: parse_gamepad(fields[2])?,
+        port_b: parse_gamepad(fields[3])?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movie::Checkpoint;
+
+    const FM2: &str = "version 3\r\nemuVersion 22020\r\nrerecordCount 12\r\npalFlag 0\r\n\
+        romFilename Some Game\r\nromChecksum base64:AAAAAAAAAAAAAAAAAAAAAA==\r\nguid 1234\r\nfourscore 0\r\n\
+        port0 1\r\nport1 1\r\nport2 0\r\ncomment checkpoint 2 00000000deadbeef\r\n\
+        |0|........|........||\r\n|1|R..U...A|    T   ||\r\n|2|.L.....A|........||\r\n";
+
+    #[test]
+    fn reads_fceux_movies() {
+        let movie = Movie::from_fm2(FM2).unwrap();
+        assert_eq!(movie.rom_filename, "Some Game");
+        assert_eq!(movie.rerecord_count, 12);
+        assert_eq!(movie.region, Region::Ntsc);
+        assert_eq!(
+            movie.checkpoints,
+            vec![Checkpoint {
+                frame: 2,
+                ram_hash: 0xDEADBEEF
+            }]
+        );
+        assert_eq!(movie.frames.len(), 3);
+        assert_eq!(movie.frames[0], MovieFrame::default());
+        assert_eq!(
+            movie.frames[1],
+            MovieFrame {
+                event: Some(ConsoleEvent::SoftReset),
+                port_a: StandardController {
+                    right: true,
+                    up: true,
+                    a: true,
+                    ..Default::default()
+                },
+                port_b: StandardController {
+                    start: true,
+                    ..Default::default()
+                },
+            }
+        );
+        assert_eq!(movie.frames[2].event, Some(ConsoleEvent::Power));
+
+        // Writing and reading back loses nothing.
+        assert_eq!(Movie::from_fm2(&movie.to_fm2().unwrap()).unwrap(), movie);
+    }
+
+    #[test]
+    fn rejects_what_it_cant_play() {
+        let unsupported =
+            |text: &str| matches!(Movie::from_fm2(text), Err(MovieError::Unsupported(_)));
+        assert!(unsupported("version 3\nfourscore 1\n"));
+        assert!(unsupported("version 3\nport1 2\n"));
+        assert!(unsupported("version 3\nsavestate base64:AAAA\n"));
+        assert!(unsupported("version 3\n|4|........|........||\n"));
+        assert!(matches!(
+            Movie::from_fm2("version 3\n|0|...|........||\n"),
+            Err(MovieError::Malformed(_))
+        ));
+
+        let movie = Movie::new(MovieStart::SaveState(vec![]), Region::Ntsc);
+        assert!(matches!(movie.to_fm2(), Err(MovieError::Unsupported(_))));
+    }
+}
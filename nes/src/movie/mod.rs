@@ -0,0 +1,242 @@
+//! Input movies: a log of what was pressed on both controllers each frame, plus resets and power cycles, that plays
+//! back into the console to reproduce a run exactly. Movies convert to and from FCEUX's .fm2 and BizHawk's .bk2.
+
+mod bk2;
+mod fm2;
+mod zip;
+
+use crate::{
+    input::StandardController,
+    nes::NES,
+    region::Region,
+    save_state::{fnv1a, SaveStateError},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    /// The file isn't a movie in the format being read. Says what was wrong with it.
+    Malformed(String),
+    /// The movie needs something this emulator or format can't represent, like a Four Score, a Zapper or an FDS
+    /// disk swap.
+    Unsupported(String),
+    /// The save state the movie starts from couldn't be loaded.
+    SaveState(SaveStateError),
+    /// The RAM didn't match a checkpoint, so playback has drifted from what was recorded.
+    Desync {
+        frame: u64,
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl From<SaveStateError> for MovieError {
+    fn from(error: SaveStateError) -> Self {
+        MovieError::SaveState(error)
+    }
+}
+
+/// Where a movie starts from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    PowerOn,
+    /// A state from [`NES::save_state`].
+    SaveState(Vec<u8>),
+}
+
+/// Something done to the console at the start of a frame, before its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleEvent {
+    SoftReset,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub event: Option<ConsoleEvent>,
+    pub port_a: StandardController,
+    pub port_b: StandardController,
+}
+
+/// A hash of the work RAM after some number of frames, to check playback against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub frame: u64,
+    pub ram_hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub start: MovieStart,
+    pub region: Region,
+    /// Kept for the movie file formats, which record it for the user's benefit.
+    pub rom_filename: String,
+    pub rerecord_count: u32,
+    pub frames: Vec<MovieFrame>,
+    /// In frame order.
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Movie {
+    pub fn new(start: MovieStart, region: Region) -> Self {
+        Self {
+            start,
+            region,
+            rom_filename: String::new(),
+            rerecord_count: 0,
+            frames: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Puts the console where the movie starts: power-on in the movie's region, or the save state.
+    pub fn start(&self, nes: &mut NES) -> Result<(), MovieError> {
+        match &self.start {
+            MovieStart::PowerOn => {
+                nes.set_region(self.region);
+                nes.power_cycle();
+            }
+            MovieStart::SaveState(state) => nes.load_state(state)?,
+        }
+        Ok(())
+    }
+
+    /// Starts the movie and plays it to the end, checking every checkpoint.
+    pub fn play(&self, nes: &mut NES) -> Result<(), MovieError> {
+        let mut player = Player::new(self, nes)?;
+        while player.play_frame(nes)? {}
+        Ok(())
+    }
+}
+
+fn ram_hash(nes: &NES) -> u64 {
+    fnv1a(&[nes.ram()])
+}
+
+fn run_frame(nes: &mut NES, frame: &MovieFrame) {
+    match frame.event {
+        Some(ConsoleEvent::SoftReset) => nes.reset(),
+        Some(ConsoleEvent::Power) => nes.power_cycle(),
+        None => {}
+    }
+    nes.update_controller_port_a(&frame.port_a);
+    nes.update_controller_port_b(&frame.port_b);
+    nes.advance_to_next_frame();
+}
+
+/// Records a movie by running the console one frame at a time.
+pub struct Recorder {
+    movie: Movie,
+    checkpoint_interval: u64,
+}
+
+impl Recorder {
+    /// Puts the console at `start` and begins recording. Every `checkpoint_interval` frames the RAM is hashed into a
+    /// checkpoint; 0 records none.
+    pub fn new(
+        nes: &mut NES,
+        start: MovieStart,
+        checkpoint_interval: u64,
+    ) -> Result<Self, MovieError> {
+        let movie = Movie::new(start, nes.region());
+        movie.start(nes)?;
+        Ok(Self {
+            movie,
+            checkpoint_interval,
+        })
+    }
+
+    /// Runs a frame with `frame`'s event and input, and adds it to the movie.
+    pub fn record_frame(&mut self, nes: &mut NES, frame: MovieFrame) {
+        run_frame(nes, &frame);
+        self.movie.frames.push(frame);
+
+        let frames = self.movie.frames.len() as u64;
+        if self.checkpoint_interval > 0 && frames.is_multiple_of(self.checkpoint_interval) {
+            self.movie.checkpoints.push(Checkpoint {
+                frame: frames,
+                ram_hash: ram_hash(nes),
+            });
+        }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Plays a movie back into the console one frame at a time.
+pub struct Player<'a> {
+    movie: &'a Movie,
+    position: usize,
+    next_checkpoint: usize,
+}
+
+impl<'a> Player<'a> {
+    /// Puts the console at the movie's start, ready to play its first frame.
+    pub fn new(movie: &'a Movie, nes: &mut NES) -> Result<Self, MovieError> {
+        movie.start(nes)?;
+        Ok(Self {
+            movie,
+            position: 0,
+            next_checkpoint: 0,
+        })
+    }
+
+    /// Runs the next frame of the movie. Returns false once there are none left, or a desync if the RAM doesn't match
+    /// a checkpoint for the frame.
+    pub fn play_frame(&mut self, nes: &mut NES) -> Result<bool, MovieError> {
+        let Some(frame) = self.movie.frames.get(self.position) else {
+            return Ok(false);
+        };
+        run_frame(nes, frame);
+        self.position += 1;
+
+        let frames = self.position as u64;
+        while let Some(checkpoint) = self.movie.checkpoints.get(self.next_checkpoint) {
+            if checkpoint.frame > frames {
+                break;
+            }
+            self.next_checkpoint += 1;
+            let found = ram_hash(nes);
+            if checkpoint.frame == frames && checkpoint.ram_hash != found {
+                return Err(MovieError::Desync {
+                    frame: frames,
+                    expected: checkpoint.ram_hash,
+                    found,
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    /// The number of frames played so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.movie.frames.len()
+    }
+}
+
+/// Reads the checkpoint lines movie files carry in their comments: "checkpoint <frame> <RAM hash in hex>".
+fn parse_checkpoint(line: &str) -> Option<Checkpoint> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "checkpoint" {
+        return None;
+    }
+    let frame = words.next()?.parse().ok()?;
+    let ram_hash = u64::from_str_radix(words.next()?, 16).ok()?;
+    Some(Checkpoint { frame, ram_hash })
+}
+
+fn format_checkpoint(checkpoint: &Checkpoint) -> String {
+    format!(
+        "checkpoint {} {:016x}",
+        checkpoint.frame, checkpoint.ram_hash
+    )
+}
@@ -0,0 +1,426 @@
+//! Just enough of the zip format for BizHawk movies: reading archives whose entries are stored or deflated, and
+//! writing archives of stored entries. Zip64, encryption and multi-disk archives aren't supported.
+
+use super::MovieError;
+
+const LOCAL_HEADER: u32 = 0x0403_4B50;
+const CENTRAL_HEADER: u32 = 0x0201_4B50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4B50;
+/// 1980-01-01, the earliest date a zip can hold, so archives don't depend on when they were written.
+const DOS_DATE: u16 = 0x0021;
+
+fn malformed(reason: &str) -> MovieError {
+    MovieError::Malformed(format!("bad zip archive: {reason}"))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, MovieError> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| malformed("truncated"))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, MovieError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| malformed("truncated"))
+}
+
+/// Reads every file in an archive, as (name, contents) pairs in central directory order.
+pub(crate) fn read(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>, MovieError> {
+    // The end of central directory record is 22 bytes, followed by a comment of up to 64 KB.
+    let end = (0..archive.len().saturating_sub(21))
+        .rev()
+        .take(0x10000)
+        .find(|offset| u32_at(archive, *offset).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| malformed("no end of central directory"))?;
+    let count = u16_at(archive, end + 10)?;
+    let mut offset = u32_at(archive, end + 16)? as usize;
+
+    let mut files = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if u32_at(archive, offset)? != CENTRAL_HEADER {
+            return Err(malformed("bad central directory header"));
+        }
+        let flags = u16_at(archive, offset + 8)?;
+        let method = u16_at(archive, offset + 10)?;
+        let crc = u32_at(archive, offset + 16)?;
+        let compressed_size = u32_at(archive, offset + 20)? as usize;
+        let size = u32_at(archive, offset + 24)? as usize;
+        let name_length = u16_at(archive, offset + 28)? as usize;
+        let extra_length = u16_at(archive, offset + 30)? as usize;
+        let comment_length = u16_at(archive, offset + 32)? as usize;
+        let local_offset = u32_at(archive, offset + 42)? as usize;
+        let name = archive
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(|| malformed("truncated"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if flags & 1 != 0 {
+            return Err(MovieError::Unsupported(format!(
+                "encrypted zip entry {name}"
+            )));
+        }
+        if u32_at(archive, local_offset)? != LOCAL_HEADER {
+            return Err(malformed("bad local header"));
+        }
+        let data_start = local_offset
+            + 30
+            + u16_at(archive, local_offset + 26)? as usize
+            + u16_at(archive, local_offset + 28)? as usize;
+        let data = archive
+            .get(data_start..data_start + compressed_size)
+            .ok_or_else(|| malformed("truncated"))?;
+
+        let contents = match method {
+            0 => data.to_vec(),
+            8 => inflate(data)?,
+            _ => {
+                return Err(MovieError::Unsupported(format!(
+                    "zip compression method {method} in {name}"
+                )))
+            }
+        };
+        if contents.len() != size || crc32(&contents) != crc {
+            return Err(malformed(&format!("{name} is corrupt")));
+        }
+        files.push((name, contents));
+    }
+    Ok(files)
+}
+
+/// Writes an archive holding `files` uncompressed.
+pub(crate) fn write(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, contents) in files {
+        let local_offset = archive.len() as u32;
+        let crc = crc32(contents);
+
+        // The local header and the central directory entry share everything from the version needed to the name
+        // length.
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(DOS_DATE.to_le_bytes());
+        common.extend(crc.to_le_bytes());
+        common.extend((contents.len() as u32).to_le_bytes());
+        common.extend((contents.len() as u32).to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        archive.extend(LOCAL_HEADER.to_le_bytes());
+        archive.extend(&common);
+        archive.extend(name.as_bytes());
+        archive.extend(*contents);
+
+        central_directory.extend(CENTRAL_HEADER.to_le_bytes());
+        central_directory.extend(20u16.to_le_bytes());
+        central_directory.extend(&common);
+        central_directory.extend([0; 10]);
+        central_directory.extend(local_offset.to_le_bytes());
+        central_directory.extend(name.as_bytes());
+    }
+
+    let central_directory_offset = archive.len() as u32;
+    archive.extend(&central_directory);
+    archive.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    archive.extend([0; 4]);
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((central_directory.len() as u32).to_le_bytes());
+    archive.extend(central_directory_offset.to_le_bytes());
+    archive.extend(0u16.to_le_bytes());
+    archive
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are sent in, most likely to be used first.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit: u8,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, MovieError> {
+        let byte = self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| malformed("deflate stream ended early"))?;
+        let bit = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.position += 1;
+        }
+        Ok(bit as u32)
+    }
+
+    /// Reads `count` bits, least significant first.
+    fn bits(&mut self, count: u8) -> Result<u32, MovieError> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code, decoded a bit at a time: codes of each length are consecutive, so it's enough to know
+/// how many there are of each length and which symbols they stand for, in order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            symbols.extend(
+                (0..lengths.len() as u16).filter(|symbol| lengths[*symbol as usize] == length),
+            );
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, MovieError> {
+        // The first code of the current length, and where its symbols start.
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for count in &self.counts[1..] {
+            code |= reader.bit()?;
+            let count = *count as u32;
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(malformed("bad Huffman code"))
+    }
+}
+
+/// Decompresses a raw deflate stream, as zip entries hold.
+fn inflate(data: &[u8]) -> Result<Vec<u8>, MovieError> {
+    let mut reader = BitReader {
+        bytes: data,
+        position: 0,
+        bit: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bit()? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let start = reader.position;
+                let length = u16_at(data, start)? as usize;
+                let stored = data
+                    .get(start + 4..start + 4 + length)
+                    .ok_or_else(|| malformed("deflate stream ended early"))?;
+                output.extend_from_slice(stored);
+                reader.position = start + 4 + length;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                inflate_block(
+                    &mut reader,
+                    &mut output,
+                    &Huffman::new(&lengths),
+                    &Huffman::new(&[5; 30]),
+                )?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(malformed("bad deflate block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), MovieError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| malformed("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(malformed("code lengths overrun"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), MovieError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASES.len() {
+                    return Err(malformed("bad length code"));
+                }
+                let length = LENGTH_BASES[i] as usize + reader.bits(LENGTH_EXTRA_BITS[i])? as usize;
+
+                let i = distances.decode(reader)? as usize;
+                if i >= DISTANCE_BASES.len() {
+                    return Err(malformed("bad distance code"));
+                }
+                let distance =
+                    DISTANCE_BASES[i] as usize + reader.bits(DISTANCE_EXTRA_BITS[i])? as usize;
+                if distance > output.len() {
+                    return Err(malformed("distance reaches back before the start"));
+                }
+
+                // Copies can overlap what they're writing, so go a byte at a time.
+                let start = output.len() - distance;
+                for j in 0..length {
+                    output.push(output[start + j]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_archives_round_trip() {
+        let files: [(&str, &[u8]); 3] = [
+            ("Header.txt", b"Platform NES\n"),
+            ("Empty.txt", b""),
+            ("Input Log.txt", &[b'.'; 1000]),
+        ];
+        let archive = write(&files);
+        let read_back = read(&archive).unwrap();
+        assert_eq!(read_back.len(), 3);
+        for ((name, contents), (read_name, read_contents)) in files.iter().zip(&read_back) {
+            assert_eq!(name, read_name);
+            assert_eq!(contents, read_contents);
+        }
+
+        let mut corrupt = archive.clone();
+        corrupt[40] ^= 1;
+        assert!(matches!(read(&corrupt), Err(MovieError::Malformed(_))));
+        assert!(read(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn inflates_each_block_type() {
+        // A stored block: "hi".
+        assert_eq!(
+            inflate(&[0x01, 0x02, 0x00, 0xFD, 0xFF, b'h', b'i']).unwrap(),
+            b"hi"
+        );
+        // Fixed codes, from zlib: "hello hello hello".
+        assert_eq!(
+            inflate(&[0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x90, 0x00]).unwrap(),
+            b"hello hello hello"
+        );
+        // Dynamic codes, from zlib at level 9.
+        let text: Vec<u8> = (0..200)
+            .flat_map(|i| {
+                format!("|..|{}|........|\n", ["U.......", "....S..A"][i % 2]).into_bytes()
+            })
+            .collect();
+        let compressed = [
+            0xED, 0xCA, 0x31, 0x0D, 0x00, 0x20, 0x10, 0x03, 0xC0, 0x1D, 0x31, 0xF5, 0x80, 0x06,
+            0x82, 0x9B, 0x17, 0xCF, 0x00, 0x2C, 0x38, 0x20, 0xB9, 0x4E, 0x6D, 0x73, 0x95, 0xD4,
+            0xCC, 0x4E, 0xE5, 0x96, 0x56, 0x67, 0x8D, 0xA4, 0x3F, 0x37, 0x4D, 0xD3, 0x34, 0x4D,
+            0xD3, 0x34, 0x4D, 0xD3, 0x34, 0x4D, 0xD3, 0x34, 0xFD, 0x83, 0x5E,
+        ];
+        assert_eq!(inflate(&compressed).unwrap(), text);
+    }
+}
@@ -1,18 +1,32 @@
 use crate::{
     cartridge::Cartridge,
+    clock::Clock,
     cpu_bus::{CpuBus, FrozenCpuBus},
-    frame::Frame,
-    input::{ControllerPort, ControllerState},
+    frame::{Frame, PixelFormat},
+    input::{ControllerPort, ControllerState, InputActivity, InputPoll},
+    interrupts::{IrqLine, IrqSource},
     memory::Ram,
+    palettes::Palette,
     ppu::PPU,
+    region::Region,
+    rewind::{RewindBuffer, RewindConfig},
+    save_state::{Header, SaveStateError, Snapshot, StateReader, StateWriter},
 };
 use macros::{cpu_bus, frozen_cpu_bus};
 use mos_6502::{
     cpu::CPU,
+    cycle_analysis::{access_cycles, crosses_page, AccessCycles},
     debugging::{Debugger, ExecutionState},
+    disassembly::Instruction,
+    memory::Bus16,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use std::{cell::RefCell, rc::Rc};
 
+/// The console. Cloning it copies the whole running machine, e.g. to explore several branches from one point; the
+/// clone gets its own copy of the debugger, if one is enabled, so the branches record separate backtraces.
 pub struct NES {
     cpu: CPU,
     ram: Ram<2048>,
@@ -21,7 +35,17 @@ pub struct NES {
     port_b: ControllerPort,
     cartridge: Box<dyn Cartridge>,
     frame: Frame,
-    debugger: Option<Rc<RefCell<Debugger>>>,
+    clock: Clock,
+    irq_line: IrqLine,
+    oam_dma_page: Option<u8>,
+    region: Region,
+    frame_count: u64,
+    lag_frames: u64,
+    input_activity: InputActivity,
+    rewind: Option<RewindBuffer>,
+    run_ahead: u32,
+    run_ahead_cost: RunAheadCost,
+    debugger: Option<Arc<Mutex<Debugger>>>,
 }
 
 impl NES {
@@ -34,16 +58,92 @@ impl NES {
             port_a: Default::default(),
             port_b: Default::default(),
             frame: Frame::new(),
+            clock: Clock::new(),
+            irq_line: IrqLine::new(),
+            oam_dma_page: None,
+            region: Region::Ntsc,
+            frame_count: 0,
+            lag_frames: 0,
+            input_activity: InputActivity::default(),
+            rewind: None,
+            run_ahead: 0,
+            run_ahead_cost: RunAheadCost::default(),
             debugger: None,
         }
     }
 
+    /// Inserts a cartridge and resets, switching to the region from the cartridge's header. Any rewind history is
+    /// dropped, since it's from the previous cartridge, and recording starts over from here.
     pub fn insert_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
+        self.set_region(cartridge.region());
         self.cartridge = cartridge;
+        {
+            let mut bus = cpu_bus!(self);
+            self.cpu.reset(&mut bus);
+        }
+
+        if let Some(config) = self.rewind.as_ref().map(RewindBuffer::config) {
+            self.enable_rewind(config);
+        }
+    }
+
+    /// Presses the reset button: the CPU jumps through its reset vector and the PPU clears its registers. RAM and the
+    /// cartridge keep their contents.
+    pub fn reset(&mut self) {
+        self.ppu.reset();
+        self.oam_dma_page = None;
         let mut bus = cpu_bus!(self);
         self.cpu.reset(&mut bus)
     }
 
+    /// Switches the console off and on again with the same cartridge, region and palette. Everything else starts
+    /// over, including the frame count. The cartridge's volatile memory (VRAM and CHR RAM) is cleared too; only
+    /// battery-backed PRG RAM survives. Any rewind history is dropped, since it's from before the power went off.
+    pub fn power_cycle(&mut self) {
+        let region = self.region;
+        let palette = self.ppu.palette().clone();
+        let mut cartridge = std::mem::take(&mut self.cartridge);
+        cartridge.power_cycle();
+        let mut nes = NES {
+            run_ahead: self.run_ahead,
+            ..NES::new()
+        };
+        if let Some(debugger) = self.debugger.take() {
+            nes.cpu.attach_debugger(Arc::clone(&debugger));
+            nes.debugger = Some(debugger);
+        }
+
+        nes.insert_cartridge(cartridge);
+        nes.set_region(region);
+        nes.set_palette(palette);
+        if let Some(rewind) = &self.rewind {
+            nes.enable_rewind(rewind.config());
+        }
+        *self = nes;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.set_region(region);
+        self.clock.set_region(region);
+    }
+
+    /// Sets the colors the PPU's output is drawn with, e.g. one loaded from a .pal file or the RGB PPU palette for a
+    /// PlayChoice-10 title.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.frame.discard_rgb8();
+        self.ppu.set_palette(palette);
+    }
+
+    /// The rate a front end should present frames at.
+    pub fn frame_rate(&self) -> f64 {
+        self.region.frame_rate()
+    }
+
     pub fn get_pc(&self) -> u16 {
         self.cpu.pc
     }
@@ -57,22 +157,51 @@ impl NES {
         self.cpu.current_state(&bus)
     }
 
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        let bus = frozen_cpu_bus!(self);
+        bus.peek_byte(address)
+    }
+
+    /// The 2 KB of work RAM.
+    pub fn ram(&self) -> &[u8] {
+        self.ram.as_slice()
+    }
+
+    /// Sprite memory, 64 entries of 4 bytes.
+    pub fn oam(&self) -> &[u8] {
+        self.ppu.oam()
+    }
+
+    /// Reads the PPU's address space (pattern tables, nametables and palette RAM) without side effects.
+    pub fn peek_ppu_memory(&self, address: u16) -> u8 {
+        self.ppu.peek_memory(self.cartridge.as_ref(), address)
+    }
+
+    pub fn palette(&self) -> &Palette {
+        self.ppu.palette()
+    }
+
     pub fn jammed(&self) -> bool {
         self.cpu.jammed
     }
 
     pub fn enable_debugger(&mut self) {
-        let debugger = Rc::new(RefCell::new(Debugger::new()));
-        self.cpu.attach_debugger(Rc::clone(&debugger));
+        let debugger = Arc::new(Mutex::new(Debugger::new()));
+        self.cpu.attach_debugger(Arc::clone(&debugger));
         self.debugger = Some(debugger);
     }
 
     pub fn dump_backtrace(&self) {
         if let Some(debugger) = &self.debugger {
-            debugger.borrow().dump_backtrace();
+            debugger.lock().unwrap().dump_backtrace();
         }
     }
 
+    /// The sources currently holding the CPU's /IRQ line low.
+    pub fn irq_sources(&self) -> impl Iterator<Item = IrqSource> + '_ {
+        self.irq_line.sources()
+    }
+
     pub fn in_vblank(&self) -> bool {
         self.ppu.in_vblank()
     }
@@ -81,20 +210,131 @@ impl NES {
         &self.frame
     }
 
+    /// The current frame as RGB888 through the active palette, converted the first time it's asked for in each frame.
+    #[deprecated(note = "use `NES::convert_frame` with `PixelFormat::Rgb888`")]
+    pub fn data_rgb8(&self) -> &[u8] {
+        self.frame.rgb8(self.ppu.palette())
+    }
+
+    /// Converts the current frame through the active palette. See [`Frame::convert`].
+    pub fn convert_frame(&self, format: PixelFormat, output: &mut [u8]) {
+        self.frame.convert(self.ppu.palette(), format, output);
+    }
+
     pub fn tick(&mut self) {
+        let access_cycles = self.predict_access_cycles();
+        self.clock.start_instruction(access_cycles);
         let cpu_cycles = {
             let mut bus = cpu_bus!(self);
             self.cpu.execute_instruction(&mut bus)
         };
 
-        let ppu_cycles = cpu_cycles * 3;
-        self.ppu
-            .tick(self.cartridge.as_mut(), &mut self.frame, ppu_cycles);
+        // The CPU polls for NMI before its last cycle, so an edge on the last cycle waits for the next instruction.
+        let end = self.clock.cycle() + cpu_cycles;
+        self.catch_up(end.saturating_sub(1));
+        self.clock.advance(cpu_cycles);
+
+        if let Some(page) = self.oam_dma_page.take() {
+            self.run_oam_dma(page);
+        }
+
+        self.cpu.nmi = self.clock.take_nmi();
+        self.irq_line
+            .set(IrqSource::Cartridge, self.cartridge.irq());
+        self.cpu.irq = self.irq_line.is_asserted();
+        self.catch_up(self.clock.cycle());
+    }
+
+    /// Works out when the next instruction will make each of its bus accesses, so PPU accesses land on the right cycle.
+    fn predict_access_cycles(&self) -> AccessCycles {
+        // Interrupt sequences only touch the stack and the vectors.
+        if self.cpu.nmi || (self.cpu.irq && !self.cpu.irq_disable) {
+            return AccessCycles::default();
+        }
+
+        let bus = frozen_cpu_bus!(self);
+        let pc = self.cpu.pc;
+        let instruction = Instruction::new(
+            bus.peek_byte(pc),
+            bus.peek_byte(pc.wrapping_add(1)),
+            bus.peek_byte(pc.wrapping_add(2)),
+        );
+        let page_crossed = crosses_page(&bus, &instruction, self.cpu.x, self.cpu.y);
+        access_cycles(&instruction, page_crossed)
+    }
 
-        self.cpu.nmi = self.ppu.interrupt;
+    fn catch_up(&mut self, cycle: u64) {
+        self.clock.catch_up(
+            &mut self.ppu,
+            self.cartridge.as_mut(),
+            &mut self.frame,
+            cycle,
+        );
     }
 
-    pub fn advance_to_next_frame(&mut self) {
+    /// Copies a page of CPU memory into OAM through $2004, starting at the current OAMADDR. The CPU is halted for one
+    /// cycle, plus one more to line up with a read cycle if needed, then alternates reads and writes for 512 cycles.
+    fn run_oam_dma(&mut self, page: u8) {
+        let alignment_cycles = 1 + self.cpu.total_cycles % 2;
+        self.stall_cpu(alignment_cycles);
+
+        let base_address = page as u16 * 256;
+        for i in 0..256 {
+            self.clock.start_instruction(AccessCycles::default());
+            let value = {
+                let mut bus = cpu_bus!(self);
+                bus.read_byte(base_address + i)
+            };
+            self.stall_cpu(1);
+
+            self.clock.start_instruction(AccessCycles::default());
+            {
+                let mut bus = cpu_bus!(self);
+                bus.write_byte(0x2004, value);
+            }
+            self.stall_cpu(1);
+        }
+    }
+
+    fn stall_cpu(&mut self, cpu_cycles: u64) {
+        self.cpu.total_cycles += cpu_cycles;
+        self.clock.advance(cpu_cycles);
+    }
+
+    /// Runs until the start of the next vblank. With run-ahead on, the frame left to show is the one that many
+    /// frames further on, but the console itself only moves forward one frame, and that's the frame reported on.
+    pub fn advance_to_next_frame(&mut self) -> FrameResult {
+        self.emulate_frame();
+        let activity = std::mem::take(&mut self.input_activity);
+
+        self.frame_count += 1;
+        let lag = !activity.read;
+        if lag {
+            self.lag_frames += 1;
+        }
+        if let Some(rewind) = &self.rewind {
+            if rewind.is_due(self.frame_count) {
+                let snapshot = self.rewind_snapshot();
+                self.rewind
+                    .as_mut()
+                    .unwrap()
+                    .push(self.frame_count, snapshot);
+            }
+        }
+
+        if self.run_ahead > 0 {
+            self.run_ahead();
+        }
+
+        FrameResult {
+            frame: self.frame_count,
+            lag,
+            polls: activity.polls,
+        }
+    }
+
+    fn emulate_frame(&mut self) {
+        self.input_activity = InputActivity::default();
         let mut last_in_vblank = self.in_vblank();
         while !self.jammed() {
             self.tick();
@@ -106,6 +346,147 @@ impl NES {
         }
     }
 
+    /// Emulates `run_ahead` frames past the current one with the same controller input, keeps the last picture, and
+    /// rolls everything else back. Games that take a few frames to react to input then appear to react sooner.
+    fn run_ahead(&mut self) {
+        let start = Instant::now();
+        let mut writer = StateWriter::new();
+        self.save(&mut writer);
+        let state = writer.into_bytes();
+        let saved = Instant::now();
+
+        for _ in 0..self.run_ahead {
+            self.emulate_frame();
+        }
+        let emulated = Instant::now();
+
+        // Loading leaves the frame alone, so the picture from the furthest frame stays.
+        self.load(&mut StateReader::new(&state))
+            .expect("a state just saved should load");
+
+        self.run_ahead_cost = RunAheadCost {
+            frames: self.run_ahead,
+            emulation: emulated - saved,
+            snapshots: (saved - start) + emulated.elapsed(),
+        };
+    }
+
+    /// How many frames ahead of the console the picture is drawn. 0 turns run-ahead off.
+    pub fn run_ahead_frames(&self) -> u32 {
+        self.run_ahead
+    }
+
+    /// Sets how many frames to run ahead, which should be at most the number of frames the game lags behind input.
+    /// Each host frame then costs that many extra frames of emulation, plus a save and a load.
+    pub fn set_run_ahead_frames(&mut self, frames: u32) {
+        self.run_ahead = frames;
+        self.run_ahead_cost = RunAheadCost::default();
+    }
+
+    /// What running ahead cost on the last call to `advance_to_next_frame`.
+    pub fn run_ahead_cost(&self) -> RunAheadCost {
+        self.run_ahead_cost
+    }
+
+    /// Frames run by `advance_to_next_frame` since power on.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Frames since power on in which the game didn't read either controller, so any input held during them was
+    /// missed.
+    pub fn lag_frame_count(&self) -> u64 {
+        self.lag_frames
+    }
+
+    /// Starts recording snapshots to rewind through, as frames are advanced.
+    pub fn enable_rewind(&mut self, config: RewindConfig) {
+        let mut rewind = RewindBuffer::new(config);
+        rewind.push(self.frame_count, self.rewind_snapshot());
+        self.rewind = Some(rewind);
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// Steps back at least `frames` frames, to the nearest snapshot before then (or the oldest one left), restoring
+    /// the picture along with the machine. Advancing frames afterwards resumes from there. Returns how many frames
+    /// were actually stepped back.
+    ///
+    /// If the snapshot can't be loaded, the console is left as it was and the rewind history is dropped, since the
+    /// rest of it is no more use.
+    pub fn rewind(&mut self, frames: u64) -> Result<u64, SaveStateError> {
+        let Some(mut rewind) = self.rewind.take() else {
+            return Ok(0);
+        };
+        let target = self.frame_count.saturating_sub(frames);
+        let Some((frame, snapshot)) = rewind.rewind_to(target) else {
+            self.rewind = Some(rewind);
+            return Ok(0);
+        };
+
+        let mut reader = StateReader::new(snapshot);
+        let mut picture = self.frame.clone();
+        picture.load(&mut reader)?;
+        let lag_frames = reader.read_u64()?;
+        self.load_state(reader.remaining())?;
+
+        self.frame = picture;
+        self.lag_frames = lag_frames;
+        let stepped_back = self.frame_count.saturating_sub(frame);
+        self.frame_count = frame;
+        self.rewind = Some(rewind);
+        Ok(stepped_back)
+    }
+
+    /// The frame and lag frame count, followed by a save state.
+    fn rewind_snapshot(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        self.frame.save(&mut writer);
+        writer.write_u64(self.lag_frames);
+        let mut snapshot = writer.into_bytes();
+        snapshot.extend(self.save_state());
+        snapshot
+    }
+
+    /// Snapshots the whole machine. The frame buffer isn't included; it's redrawn by the next frame.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        Header {
+            rom_hash: self.cartridge.rom_hash(),
+        }
+        .save(&mut writer);
+        self.save(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// Restores a snapshot from `save_state`. It has to have been taken with the same ROM and format version; if it
+    /// can't be loaded, the console is left as it was.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), SaveStateError> {
+        let mut reader = StateReader::new(state);
+        Header::load(&mut reader, self.cartridge.rom_hash())?;
+
+        let backup = self.save_state();
+        let result = self
+            .load(&mut reader)
+            .and_then(|_| match reader.is_empty() {
+                true => Ok(()),
+                false => Err(SaveStateError::Malformed),
+            });
+        if result.is_err() {
+            let mut reader = StateReader::new(&backup);
+            Header::load(&mut reader, self.cartridge.rom_hash())
+                .and_then(|_| self.load(&mut reader))
+                .expect("a state just saved should load");
+        }
+        result
+    }
+
     pub fn update_controller_port_a<S: ControllerState>(&mut self, state: &S) {
         self.port_a.update(state);
     }
@@ -115,6 +496,148 @@ impl NES {
     }
 }
 
+/// What happened during a frame run by `advance_to_next_frame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameResult {
+    /// The frame count, including this frame.
+    pub frame: u64,
+    /// Whether this was a lag frame: the game never read the controllers, so the input for it went unseen.
+    pub lag: bool,
+    /// Each time the game strobed the controllers, in order. The gap from here to the end of the frame is part of
+    /// the input latency.
+    pub polls: Vec<InputPoll>,
+}
+
+/// The extra work run-ahead does for a host frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunAheadCost {
+    /// Frames emulated on top of the real one.
+    pub frames: u32,
+    pub emulation: Duration,
+    /// Time spent saving and restoring the state.
+    pub snapshots: Duration,
+}
+
+impl Clone for NES {
+    fn clone(&self) -> Self {
+        let mut cpu = self.cpu.clone();
+        let debugger = self
+            .debugger
+            .as_ref()
+            .map(|debugger| Arc::new(Mutex::new(debugger.lock().unwrap().clone())));
+        match &debugger {
+            Some(debugger) => cpu.attach_debugger(Arc::clone(debugger)),
+            None => cpu.detach_debugger(),
+        }
+
+        Self {
+            cpu,
+            ram: self.ram.clone(),
+            ppu: self.ppu.clone(),
+            port_a: self.port_a.clone(),
+            port_b: self.port_b.clone(),
+            cartridge: self.cartridge.clone(),
+            frame: self.frame.clone(),
+            clock: self.clock.clone(),
+            irq_line: self.irq_line,
+            oam_dma_page: self.oam_dma_page,
+            region: self.region,
+            frame_count: self.frame_count,
+            lag_frames: self.lag_frames,
+            input_activity: self.input_activity.clone(),
+            rewind: self.rewind.clone(),
+            run_ahead: self.run_ahead,
+            run_ahead_cost: self.run_ahead_cost,
+            debugger,
+        }
+    }
+}
+
+impl RunAheadCost {
+    pub fn total(&self) -> Duration {
+        self.emulation + self.snapshots
+    }
+}
+
+impl Snapshot for NES {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(match self.region {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 2,
+        });
+        self.cpu.save(writer);
+        self.ram.save(writer);
+        self.ppu.save(writer);
+        self.port_a.save(writer);
+        self.port_b.save(writer);
+        self.cartridge.save_state(writer);
+        self.clock.save(writer);
+        self.irq_line.save(writer);
+        match self.oam_dma_page {
+            Some(page) => {
+                writer.write_bool(true);
+                writer.write_u8(page);
+            }
+            None => writer.write_bool(false),
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        let region = match reader.read_u8()? {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Dendy,
+            _ => return Err(SaveStateError::Malformed),
+        };
+        self.set_region(region);
+        self.cpu.load(reader)?;
+        self.ram.load(reader)?;
+        self.ppu.load(reader)?;
+        self.port_a.load(reader)?;
+        self.port_b.load(reader)?;
+        self.cartridge.load_state(reader)?;
+        self.clock.load(reader)?;
+        self.irq_line.load(reader)?;
+        self.oam_dma_page = match reader.read_bool()? {
+            true => Some(reader.read_u8()?),
+            false => None,
+        };
+        Ok(())
+    }
+}
+
+impl Snapshot for CPU {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.a);
+        writer.write_u8(self.x);
+        writer.write_u8(self.y);
+        writer.write_u16(self.pc);
+        writer.write_u8(self.s);
+        writer.write_u8(self.status_register());
+        writer.write_bool(self.nmi);
+        writer.write_bool(self.last_nmi);
+        writer.write_bool(self.irq);
+        writer.write_u64(self.total_cycles);
+        writer.write_bool(self.jammed);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.a = reader.read_u8()?;
+        self.x = reader.read_u8()?;
+        self.y = reader.read_u8()?;
+        self.pc = reader.read_u16()?;
+        self.s = reader.read_u8()?;
+        self.set_status_register(reader.read_u8()?);
+        self.nmi = reader.read_bool()?;
+        self.last_nmi = reader.read_bool()?;
+        self.irq = reader.read_bool()?;
+        self.total_cycles = reader.read_u64()?;
+        self.jammed = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 mod macros {
     macro_rules! cpu_bus {
         ($nes:expr) => {
@@ -124,6 +647,10 @@ mod macros {
                 port_a: &mut $nes.port_a,
                 port_b: &mut $nes.port_b,
                 cartridge: $nes.cartridge.as_mut(),
+                frame: &mut $nes.frame,
+                clock: &mut $nes.clock,
+                oam_dma_page: &mut $nes.oam_dma_page,
+                input_activity: &mut $nes.input_activity,
             }
         };
     }
@@ -143,3 +670,72 @@ mod macros {
     pub(super) use cpu_bus;
     pub(super) use frozen_cpu_bus;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::NES;
+    use crate::{
+        cartridge::Cartridge, frame::PixelFormat, palettes::Palette, test_fixtures::nestest,
+    };
+
+    #[test]
+    fn clones_record_their_own_backtraces() {
+        let mut nes = nestest();
+        nes.enable_debugger();
+        nes.advance_to_next_frame();
+
+        let backtrace = |nes: &NES| {
+            nes.debugger
+                .as_ref()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .states
+                .clone()
+        };
+        let original = backtrace(&nes);
+        let mut clone = nes.clone();
+        assert_eq!(backtrace(&clone), original);
+
+        // Stepping the clone leaves the original's backtrace alone.
+        clone.advance_to_next_frame();
+        assert_ne!(backtrace(&clone), original);
+        assert_eq!(backtrace(&nes), original);
+
+        nes.advance_to_next_frame();
+        assert_eq!(backtrace(&nes), backtrace(&clone));
+    }
+
+    #[test]
+    fn power_cycling_keeps_only_battery_backed_memory() {
+        let mut bytes = std::fs::read("test-roms/nestest/nestest.nes").unwrap();
+        // Flags 6, bit 1: battery-backed PRG RAM.
+        bytes[6] |= 0x02;
+        let mut nes = NES::new();
+        nes.insert_cartridge(<dyn Cartridge>::load(bytes).unwrap());
+
+        nes.cartridge.cpu_write(0x6000, 0x42);
+        nes.cartridge.ppu_write(0x2000, 0x24);
+        nes.power_cycle();
+
+        assert_eq!(nes.cartridge.cpu_peek(0x6000), 0x42);
+        assert_eq!(nes.cartridge.ppu_peek(0x2000), 0x00);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn rgb8_data_uses_the_active_palette() {
+        let mut nes = nestest();
+        nes.advance_to_next_frame();
+        let converted = |nes: &NES| {
+            let mut output = vec![0; 256 * 240 * 3];
+            nes.convert_frame(PixelFormat::Rgb888, &mut output);
+            output
+        };
+        assert_eq!(nes.data_rgb8(), converted(&nes));
+
+        nes.set_palette(Palette::from_pal_file(&[0x12; 192]).unwrap());
+        assert_eq!(nes.data_rgb8(), converted(&nes));
+        assert!(nes.data_rgb8().iter().all(|&byte| byte == 0x12));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    frame::Frame,
+    palettes::{composite_level, demodulation_carrier, yiq_to_rgb, NtscPaletteParameters, Palette},
+};
+
+/// How the picture gets from the console to the TV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NtscPreset {
+    /// Luma and chroma share one wire. The TV can't fully separate them, so sharp edges pick up color fringes, fine
+    /// dither patterns blend into new colors, and the artifacts crawl as the subcarrier phase moves between frames.
+    Composite,
+    /// Luma and chroma on separate wires. Color still bleeds because chroma has little bandwidth, but luma is sharp
+    /// and nothing crawls.
+    SVideo,
+    /// Clean RGB, as from an RGB-modded console, scaled to the same width as the other presets.
+    Rgb,
+}
+
+/// An output stage that rebuilds the PPU's composite video signal from a frame's color indices and decodes it like
+/// a TV would, in the spirit of blargg's nes_ntsc.
+pub struct NtscFilter {
+    preset: NtscPreset,
+    parameters: NtscPaletteParameters,
+    palette: Palette,
+    /// The cosine and sine carriers for each of the 12 subcarrier phases.
+    carriers: [(f64, f64); 12],
+    /// The composite signal of every 9-bit color at each phase, and its average (the color's luma).
+    levels: Vec<[f64; 12]>,
+    lumas: Vec<f64>,
+    signal: Vec<f64>,
+    output: Vec<u8>,
+}
+
+impl NtscFilter {
+    /// Output pixels per input pixel.
+    pub const SCALE: usize = 2;
+    pub const OUTPUT_WIDTH: usize = Frame::WIDTH * NtscFilter::SCALE;
+    pub const OUTPUT_HEIGHT: usize = Frame::HEIGHT;
+    /// The output is RGB8.
+    pub const BYTES_PER_PIXEL: usize = 3;
+
+    /// Signal samples per pixel. The PPU generates the signal at 12 phases per subcarrier cycle, which is 8 per dot.
+    const SAMPLES_PER_PIXEL: usize = 8;
+    /// Samples of the picture's black border on either side of a line, enough to cover the decoding window.
+    const BORDER_SAMPLES: usize = 12;
+    /// Chroma is averaged over two subcarrier cycles, which is what limits its bandwidth.
+    const CHROMA_WINDOW: usize = 24;
+    const LUMA_WINDOW: usize = 12;
+
+    pub fn new(preset: NtscPreset) -> Self {
+        Self::with_parameters(preset, NtscPaletteParameters::default())
+    }
+
+    pub fn with_parameters(preset: NtscPreset, parameters: NtscPaletteParameters) -> Self {
+        let mut carriers = [(0.0, 0.0); 12];
+        for (phase, carrier) in carriers.iter_mut().enumerate() {
+            *carrier = demodulation_carrier(phase, &parameters);
+        }
+
+        let levels: Vec<[f64; 12]> = (0..Palette::SIZE as u16)
+            .map(|color_index| std::array::from_fn(|phase| composite_level(color_index, phase)))
+            .collect();
+        let lumas = levels
+            .iter()
+            .map(|levels| levels.iter().sum::<f64>() / 12.0)
+            .collect();
+
+        let line_samples =
+            Frame::WIDTH * NtscFilter::SAMPLES_PER_PIXEL + 2 * NtscFilter::BORDER_SAMPLES;
+        Self {
+            preset,
+            parameters,
+            palette: Palette::generate_ntsc(&parameters),
+            carriers,
+            levels,
+            lumas,
+            signal: vec![0.0; line_samples],
+            output: vec![
+                0;
+                NtscFilter::OUTPUT_WIDTH
+                    * NtscFilter::OUTPUT_HEIGHT
+                    * NtscFilter::BYTES_PER_PIXEL
+            ],
+        }
+    }
+
+    pub fn preset(&self) -> NtscPreset {
+        self.preset
+    }
+
+    /// Runs a frame through the filter and returns the RGB8 image, `OUTPUT_WIDTH` by `OUTPUT_HEIGHT`.
+    pub fn apply(&mut self, frame: &Frame) -> &[u8] {
+        for y in 0..Frame::HEIGHT {
+            let row = &frame.color_indices()[y * Frame::WIDTH..(y + 1) * Frame::WIDTH];
+            // Each line is 341 dots of 8 phases long, which moves the next line's phase on by 4.
+            let line_phase = (frame.phase() as usize + y * 4) % 12;
+
+            match self.preset {
+                NtscPreset::Composite => self.decode_composite_line(row, line_phase, y),
+                NtscPreset::SVideo => self.decode_s_video_line(row, line_phase, y),
+                NtscPreset::Rgb => self.scale_rgb_line(row, y),
+            }
+        }
+        &self.output
+    }
+
+    /// The subcarrier phase of a sample in the line buffer, which starts `BORDER_SAMPLES` before the first pixel.
+    fn sample_phase(line_phase: usize, sample: usize) -> usize {
+        (line_phase + sample + 12 * 2 - NtscFilter::BORDER_SAMPLES) % 12
+    }
+
+    /// Fills the line buffer with the signal for a row of pixels. With `chroma_only`, each color's luma is taken out,
+    /// leaving what an S-Video chroma wire carries.
+    fn generate_signal(&mut self, row: &[u16], line_phase: usize, chroma_only: bool) {
+        let border = NtscFilter::BORDER_SAMPLES;
+        for (sample, value) in self.signal.iter_mut().enumerate() {
+            let color_index = match sample.checked_sub(border) {
+                Some(offset) if offset < Frame::WIDTH * NtscFilter::SAMPLES_PER_PIXEL => {
+                    row[offset / NtscFilter::SAMPLES_PER_PIXEL] as usize % Palette::SIZE
+                }
+                _ => 0x0F,
+            };
+            let phase = NtscFilter::sample_phase(line_phase, sample);
+            *value = match chroma_only {
+                true => self.levels[color_index][phase] - self.lumas[color_index],
+                false => self.levels[color_index][phase],
+            };
+        }
+    }
+
+    /// The buffer index of the sample at the center of an output pixel.
+    fn output_sample(output_x: usize) -> usize {
+        let samples_per_output = NtscFilter::SAMPLES_PER_PIXEL / NtscFilter::SCALE;
+        NtscFilter::BORDER_SAMPLES + output_x * samples_per_output + samples_per_output / 2
+    }
+
+    /// Demodulates I and Q around a sample by averaging the signal against the carriers.
+    fn demodulate(&self, center: usize, line_phase: usize) -> (f64, f64) {
+        let start = center.saturating_sub(NtscFilter::CHROMA_WINDOW / 2);
+        let end = (start + NtscFilter::CHROMA_WINDOW).min(self.signal.len());
+        let (mut i, mut q) = (0.0, 0.0);
+        for sample in start..end {
+            let (cos, sin) = self.carriers[NtscFilter::sample_phase(line_phase, sample)];
+            i += self.signal[sample] * cos;
+            q += self.signal[sample] * sin;
+        }
+        let length = (end - start) as f64;
+        (i / length, q / length)
+    }
+
+    fn decode_composite_line(&mut self, row: &[u16], line_phase: usize, y: usize) {
+        self.generate_signal(row, line_phase, false);
+
+        for output_x in 0..NtscFilter::OUTPUT_WIDTH {
+            let center = NtscFilter::output_sample(output_x);
+
+            // Averaging over a whole subcarrier cycle cancels chroma out of luma, except across edges, where it leaks
+            // through as fringes.
+            let start = center.saturating_sub(NtscFilter::LUMA_WINDOW / 2);
+            let end = (start + NtscFilter::LUMA_WINDOW).min(self.signal.len());
+            let luma = self.signal[start..end].iter().sum::<f64>() / (end - start) as f64;
+
+            let (i, q) = self.demodulate(center, line_phase);
+            self.write_output(output_x, y, yiq_to_rgb(luma, i, q, &self.parameters));
+        }
+    }
+
+    fn decode_s_video_line(&mut self, row: &[u16], line_phase: usize, y: usize) {
+        self.generate_signal(row, line_phase, true);
+
+        for output_x in 0..NtscFilter::OUTPUT_WIDTH {
+            let luma = self.lumas[row[output_x / NtscFilter::SCALE] as usize % Palette::SIZE];
+            let (i, q) = self.demodulate(NtscFilter::output_sample(output_x), line_phase);
+            self.write_output(output_x, y, yiq_to_rgb(luma, i, q, &self.parameters));
+        }
+    }
+
+    fn scale_rgb_line(&mut self, row: &[u16], y: usize) {
+        for output_x in 0..NtscFilter::OUTPUT_WIDTH {
+            let color = self.palette.color(row[output_x / NtscFilter::SCALE]);
+            self.write_output(output_x, y, color);
+        }
+    }
+
+    fn write_output(&mut self, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+        let base_idx = (y * NtscFilter::OUTPUT_WIDTH + x) * NtscFilter::BYTES_PER_PIXEL;
+        self.output[base_idx] = r;
+        self.output[base_idx + 1] = g;
+        self.output[base_idx + 2] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_with(pixel: impl Fn(usize) -> u16, phase: u8) -> Frame {
+        let mut frame = Frame::new();
+        for y in 0..Frame::HEIGHT {
+            for x in 0..Frame::WIDTH {
+                frame.write(x, y, pixel(x));
+            }
+        }
+        frame.set_phase(phase);
+        frame
+    }
+
+    fn output_pixel(output: &[u8], x: usize, y: usize) -> (u8, u8, u8) {
+        let i = (y * NtscFilter::OUTPUT_WIDTH + x) * NtscFilter::BYTES_PER_PIXEL;
+        (output[i], output[i + 1], output[i + 2])
+    }
+
+    #[test]
+    fn flat_colors_decode_to_the_palette() {
+        let palette = Palette::ntsc();
+        let frame = frame_with(|_| 0x16, 0);
+        for preset in [NtscPreset::Composite, NtscPreset::SVideo, NtscPreset::Rgb] {
+            let mut filter = NtscFilter::new(preset);
+            let (r, g, b) = output_pixel(filter.apply(&frame), 256, 100);
+            let (expected_r, expected_g, expected_b) = palette.color(0x16);
+            assert!(r.abs_diff(expected_r) <= 2, "{preset:?}");
+            assert!(g.abs_diff(expected_g) <= 2, "{preset:?}");
+            assert!(b.abs_diff(expected_b) <= 2, "{preset:?}");
+        }
+    }
+
+    #[test]
+    fn composite_artifacts_crawl() {
+        // A vertical edge between black and white.
+        let edge = |phase| frame_with(|x| if x < 128 { 0x0F } else { 0x30 }, phase);
+        let edge_pixels = |filter: &mut NtscFilter, phase| {
+            let output = filter.apply(&edge(phase)).to_vec();
+            (250..262)
+                .map(|x| output_pixel(&output, x, 0))
+                .collect::<Vec<_>>()
+        };
+
+        // Composite fringes the edge with color that depends on the subcarrier phase.
+        let mut composite = NtscFilter::new(NtscPreset::Composite);
+        let fringe = edge_pixels(&mut composite, 0);
+        assert!(fringe.iter().any(|(r, g, b)| r != g || g != b));
+        assert_ne!(fringe, edge_pixels(&mut composite, 4));
+
+        // With luma on its own wire the edge stays put.
+        let mut s_video = NtscFilter::new(NtscPreset::SVideo);
+        assert_eq!(edge_pixels(&mut s_video, 0), edge_pixels(&mut s_video, 4));
+    }
+}
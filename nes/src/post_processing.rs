@@ -0,0 +1,733 @@
+use crate::{
+    frame::{Frame, PixelFormat},
+    palettes::Palette,
+};
+
+/// An RGB image between post-processing stages, with each pixel packed as `0x00RRGGBB`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn from_frame(frame: &Frame, palette: &Palette) -> Self {
+        let mut image = Image::new(0, 0);
+        image.copy_frame(frame, palette);
+        image
+    }
+
+    /// Overwrites the image with a frame's colors, reusing its allocation.
+    pub fn copy_frame(&mut self, frame: &Frame, palette: &Palette) {
+        self.resize(Frame::WIDTH, Frame::HEIGHT);
+        for (pixel, color_index) in self.pixels.iter_mut().zip(frame.color_indices()) {
+            *pixel = pack(palette.color(*color_index));
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        unpack(self.pixels[y * self.width + x])
+    }
+
+    /// Converts the image to `format`, like [`Frame::convert`]. `output` must hold exactly
+    /// `width * height * format.bytes_per_pixel()` bytes.
+    pub fn convert(&self, format: PixelFormat, output: &mut [u8]) {
+        let bytes_per_pixel = format.bytes_per_pixel();
+        assert_eq!(output.len(), self.pixels.len() * bytes_per_pixel);
+
+        for (out, pixel) in output.chunks_exact_mut(bytes_per_pixel).zip(&self.pixels) {
+            out.copy_from_slice(&format.encode(unpack(*pixel))[..bytes_per_pixel]);
+        }
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels.resize(width * height, 0);
+    }
+
+    /// The pixel at (x, y), with coordinates outside the image clamped to its edges.
+    fn clamped(&self, x: isize, y: isize) -> u32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// A post-processing stage. Filters write into an output image they size themselves, so a chain can reuse its
+/// buffers from frame to frame.
+pub trait Filter {
+    fn apply(&mut self, input: &Image, output: &mut Image);
+}
+
+/// Filters run one after another, e.g. Scale2x into the CRT filter.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+    images: Vec<Image>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, filter: impl Filter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Runs a frame through every filter and returns the last image. With no filters this is the frame as is.
+    pub fn apply(&mut self, frame: &Frame, palette: &Palette) -> &Image {
+        self.images.resize(self.filters.len() + 1, Image::new(0, 0));
+        self.images[0].copy_frame(frame, palette);
+
+        for (i, filter) in self.filters.iter_mut().enumerate() {
+            let (inputs, outputs) = self.images.split_at_mut(i + 1);
+            filter.apply(&inputs[i], &mut outputs[0]);
+        }
+        &self.images[self.filters.len()]
+    }
+}
+
+/// Scale2x (also known as EPX): doubles the image and rounds off diagonal staircases without blending, so the output
+/// only uses colors from the input.
+pub struct Scale2x;
+
+impl Filter for Scale2x {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        output.resize(input.width * 2, input.height * 2);
+        let width = output.width;
+
+        for y in 0..input.height as isize {
+            for x in 0..input.width as isize {
+                let b = input.clamped(x, y - 1);
+                let d = input.clamped(x - 1, y);
+                let e = input.clamped(x, y);
+                let f = input.clamped(x + 1, y);
+                let h = input.clamped(x, y + 1);
+
+                let block = match b != h && d != f {
+                    true => [
+                        if d == b { d } else { e },
+                        if b == f { f } else { e },
+                        if d == h { d } else { e },
+                        if h == f { f } else { e },
+                    ],
+                    false => [e; 4],
+                };
+
+                let top = (y as usize * 2) * width + x as usize * 2;
+                output.pixels[top..top + 2].copy_from_slice(&block[..2]);
+                output.pixels[top + width..top + width + 2].copy_from_slice(&block[2..]);
+            }
+        }
+    }
+}
+
+/// Scale3x: Scale2x's rules extended to a 3x3 block per pixel.
+pub struct Scale3x;
+
+impl Filter for Scale3x {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        output.resize(input.width * 3, input.height * 3);
+        let width = output.width;
+
+        for y in 0..input.height as isize {
+            for x in 0..input.width as isize {
+                let a = input.clamped(x - 1, y - 1);
+                let b = input.clamped(x, y - 1);
+                let c = input.clamped(x + 1, y - 1);
+                let d = input.clamped(x - 1, y);
+                let e = input.clamped(x, y);
+                let f = input.clamped(x + 1, y);
+                let g = input.clamped(x - 1, y + 1);
+                let h = input.clamped(x, y + 1);
+                let i = input.clamped(x + 1, y + 1);
+
+                let block = match b != h && d != f {
+                    true => [
+                        if d == b { d } else { e },
+                        if (d == b && e != c) || (b == f && e != a) {
+                            b
+                        } else {
+                            e
+                        },
+                        if b == f { f } else { e },
+                        if (d == b && e != g) || (d == h && e != a) {
+                            d
+                        } else {
+                            e
+                        },
+                        e,
+                        if (b == f && e != i) || (h == f && e != c) {
+                            f
+                        } else {
+                            e
+                        },
+                        if d == h { d } else { e },
+                        if (d == h && e != i) || (h == f && e != g) {
+                            h
+                        } else {
+                            e
+                        },
+                        if h == f { f } else { e },
+                    ],
+                    false => [e; 9],
+                };
+
+                let top = (y as usize * 3) * width + x as usize * 3;
+                for row in 0..3 {
+                    let start = top + row * width;
+                    output.pixels[start..start + 3].copy_from_slice(&block[row * 3..row * 3 + 3]);
+                }
+            }
+        }
+    }
+}
+
+/// Hyllian's 2xBR (level 1). Each corner of a doubled pixel checks which way the edge through it runs, weighing
+/// color differences across a 5x5 neighborhood, and blends toward the neighbor across the edge, which smooths
+/// diagonals into shallow slopes rather than Scale2x's 45 degree steps.
+pub struct Xbr2x;
+
+impl Xbr2x {
+    /// The color of the corner of (x, y) that points along the rotated (1, 1).
+    fn corner(input: &Image, x: isize, y: isize, turns: usize) -> u32 {
+        let at = |offset: (isize, isize)| {
+            let (dx, dy) = rotate(offset, turns);
+            input.clamped(x + dx, y + dy)
+        };
+
+        let e = at((0, 0));
+        let f = at((1, 0));
+        let h = at((0, 1));
+        if e == f || e == h {
+            return e;
+        }
+
+        let b = at((0, -1));
+        let c = at((1, -1));
+        let d = at((-1, 0));
+        let g = at((-1, 1));
+        let i = at((1, 1));
+        let f4 = at((2, 0));
+        let i4 = at((2, 1));
+        let h5 = at((0, 2));
+        let i5 = at((1, 2));
+
+        // The weight of an edge running from F to H, against one running from E to I.
+        let across = distance(e, c)
+            + distance(e, g)
+            + distance(i, f4)
+            + distance(i, h5)
+            + 4 * distance(h, f);
+        let along = distance(h, d)
+            + distance(h, i5)
+            + distance(f, i4)
+            + distance(f, b)
+            + 4 * distance(e, i);
+        if across >= along {
+            return e;
+        }
+
+        let neighbor = if distance(e, f) <= distance(e, h) {
+            f
+        } else {
+            h
+        };
+        blend(e, neighbor)
+    }
+}
+
+impl Filter for Xbr2x {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        output.resize(input.width * 2, input.height * 2);
+        let width = output.width;
+
+        for y in 0..input.height as isize {
+            for x in 0..input.width as isize {
+                let top = (y as usize * 2) * width + x as usize * 2;
+                // Bottom right, bottom left, top left and top right, in rotation order.
+                output.pixels[top + width + 1] = Xbr2x::corner(input, x, y, 0);
+                output.pixels[top + width] = Xbr2x::corner(input, x, y, 1);
+                output.pixels[top] = Xbr2x::corner(input, x, y, 2);
+                output.pixels[top + 1] = Xbr2x::corner(input, x, y, 3);
+            }
+        }
+    }
+}
+
+/// hq2x, after Maxim Stepin's hqx. Neighbors count as different from the center pixel when they differ by more than
+/// a threshold in brightness or either color difference; which neighbors differ decides how each corner of the
+/// doubled pixel blends with them. The original looks this up in a table per neighbor pattern; here the rules are
+/// written out for one corner and rotated to the others.
+pub struct Hq2x;
+
+impl Hq2x {
+    /// The color of the corner of (x, y) that points along the rotated (1, 1).
+    fn corner(input: &Image, x: isize, y: isize, turns: usize) -> u32 {
+        let at = |offset: (isize, isize)| {
+            let (dx, dy) = rotate(offset, turns);
+            input.clamped(x + dx, y + dy)
+        };
+        let (e, f, h, i) = (at((0, 0)), at((1, 0)), at((0, 1)), at((1, 1)));
+        let i_differs = !similar(e, i);
+
+        match (!similar(e, f), !similar(e, h)) {
+            (false, false) => mix(&[(e, 2), (f, 1), (h, 1)]),
+            (true, false) if i_differs => mix(&[(e, 3), (h, 1)]),
+            (true, false) => mix(&[(e, 2), (h, 1), (i, 1)]),
+            (false, true) if i_differs => mix(&[(e, 3), (f, 1)]),
+            (false, true) => mix(&[(e, 2), (f, 1), (i, 1)]),
+            // An edge cuts across the corner: round it off, strongly if the far side is solid, gently past a thin
+            // line.
+            (true, true) if similar(f, h) && i_differs => mix(&[(e, 2), (f, 7), (h, 7)]),
+            (true, true) if similar(f, h) => mix(&[(e, 6), (f, 1), (h, 1)]),
+            (true, true) if i_differs => e,
+            (true, true) => mix(&[(e, 3), (i, 1)]),
+        }
+    }
+}
+
+impl Filter for Hq2x {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        output.resize(input.width * 2, input.height * 2);
+        let width = output.width;
+
+        for y in 0..input.height as isize {
+            for x in 0..input.width as isize {
+                let top = (y as usize * 2) * width + x as usize * 2;
+                output.pixels[top + width + 1] = Hq2x::corner(input, x, y, 0);
+                output.pixels[top + width] = Hq2x::corner(input, x, y, 1);
+                output.pixels[top] = Hq2x::corner(input, x, y, 2);
+                output.pixels[top + 1] = Hq2x::corner(input, x, y, 3);
+            }
+        }
+    }
+}
+
+/// hq3x: [`Hq2x`]'s approach with a 3x3 block per pixel. The center keeps its color, corners round off edges that
+/// cut across them, and the sides pick up a little of the neighbor beyond them where an edge runs into it.
+pub struct Hq3x;
+
+impl Hq3x {
+    /// The corner of (x, y) along the rotated (1, 1), and the side along the rotated (1, 0).
+    fn corner_and_side(input: &Image, x: isize, y: isize, turns: usize) -> (u32, u32) {
+        let at = |offset: (isize, isize)| {
+            let (dx, dy) = rotate(offset, turns);
+            input.clamped(x + dx, y + dy)
+        };
+        let (e, f, h, i) = (at((0, 0)), at((1, 0)), at((0, 1)), at((1, 1)));
+        let b = at((0, -1));
+        let (b_differs, f_differs, h_differs) = (!similar(e, b), !similar(e, f), !similar(e, h));
+        let i_differs = !similar(e, i);
+
+        let corner = match (f_differs, h_differs) {
+            (true, true) if similar(f, h) && i_differs => mix(&[(e, 2), (f, 7), (h, 7)]),
+            (true, true) if similar(f, h) => mix(&[(e, 2), (f, 1), (h, 1)]),
+            (true, true) => e,
+            _ => mix(&[(e, 3), (i, 1)]),
+        };
+
+        let edge_below = h_differs && similar(f, h);
+        let edge_above = b_differs && similar(f, b);
+        let side = match (f_differs, edge_below, edge_above) {
+            (true, true, true) => mix(&[(e, 3), (f, 1)]),
+            (true, true, false) | (true, false, true) => mix(&[(e, 7), (f, 1)]),
+            _ => e,
+        };
+        (corner, side)
+    }
+}
+
+impl Filter for Hq3x {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        output.resize(input.width * 3, input.height * 3);
+        let width = output.width;
+
+        for y in 0..input.height as isize {
+            for x in 0..input.width as isize {
+                let center = (y as usize * 3 + 1) * width + x as usize * 3 + 1;
+                output.pixels[center] = input.clamped(x, y);
+                for turns in 0..4 {
+                    let (corner, side) = Hq3x::corner_and_side(input, x, y, turns);
+                    let at = |(dx, dy): (isize, isize)| {
+                        let (dx, dy) = rotate((dx, dy), turns);
+                        (center as isize + dy * width as isize + dx) as usize
+                    };
+                    output.pixels[at((1, 1))] = corner;
+                    output.pixels[at((1, 0))] = side;
+                }
+            }
+        }
+    }
+}
+
+/// Settings for [`Crt`]. Intensities run from 0 (off) to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtParameters {
+    /// How dark the gaps between scanlines are.
+    pub scanline_intensity: f32,
+    /// How strongly the shadow mask's red, green and blue stripes tint each output column.
+    pub mask_intensity: f32,
+    /// How much bright areas glow into their surroundings.
+    pub bloom: f32,
+}
+
+impl Default for CrtParameters {
+    fn default() -> Self {
+        Self {
+            scanline_intensity: 0.5,
+            mask_intensity: 0.3,
+            bloom: 0.25,
+        }
+    }
+}
+
+/// A CRT look: each pixel becomes a 3x3 block whose bottom row is a dimmed scanline gap, columns are tinted by an
+/// aperture-grille style shadow mask, and a blurred copy of the image is added back on top as bloom.
+pub struct Crt {
+    parameters: CrtParameters,
+    /// Per output row and column of a block, the brightness of each channel, in 8.8 fixed point.
+    weights: [[[u32; 3]; 3]; 3],
+    glow: Vec<[u16; 3]>,
+    scratch: Vec<[u16; 3]>,
+}
+
+impl Crt {
+    pub const SCALE: usize = 3;
+    /// The bloom blur's radius, in input pixels.
+    const BLOOM_RADIUS: usize = 2;
+
+    pub fn new(parameters: CrtParameters) -> Self {
+        let mut weights = [[[0; 3]; 3]; 3];
+        for (row, row_weights) in weights.iter_mut().enumerate() {
+            let scanline = match row {
+                2 => 1.0 - parameters.scanline_intensity,
+                _ => 1.0,
+            };
+            for (column, channels) in row_weights.iter_mut().enumerate() {
+                for (channel, weight) in channels.iter_mut().enumerate() {
+                    let mask = match channel == column {
+                        true => 1.0,
+                        false => 1.0 - parameters.mask_intensity,
+                    };
+                    // Boost everything a little to make up for the light the mask and scanlines take away.
+                    let boost =
+                        1.0 + parameters.mask_intensity / 3.0 + parameters.scanline_intensity / 6.0;
+                    *weight = (scanline * mask * boost * 256.0) as u32;
+                }
+            }
+        }
+
+        Self {
+            parameters,
+            weights,
+            glow: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn parameters(&self) -> CrtParameters {
+        self.parameters
+    }
+
+    /// Box blurs the input into `glow`, horizontally then vertically, scaled by the bloom amount.
+    fn blur(&mut self, input: &Image) {
+        let (width, height) = (input.width, input.height);
+        let radius = Crt::BLOOM_RADIUS as isize;
+        let taps = (2 * radius + 1) as u32;
+        let bloom = (self.parameters.bloom * 256.0) as u32;
+
+        self.scratch.resize(width * height, [0; 3]);
+        self.glow.resize(width * height, [0; 3]);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for dx in -radius..=radius {
+                    let (r, g, b) = unpack(input.clamped(x as isize + dx, y as isize));
+                    sum[0] += r as u32;
+                    sum[1] += g as u32;
+                    sum[2] += b as u32;
+                }
+                self.scratch[y * width + x] = sum.map(|channel| (channel / taps) as u16);
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for dy in -radius..=radius {
+                    let row = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                    let channels = self.scratch[row * width + x];
+                    for (total, channel) in sum.iter_mut().zip(channels) {
+                        *total += channel as u32;
+                    }
+                }
+                self.glow[y * width + x] =
+                    sum.map(|channel| (((channel / taps) * bloom) >> 8) as u16);
+            }
+        }
+    }
+}
+
+impl Filter for Crt {
+    fn apply(&mut self, input: &Image, output: &mut Image) {
+        self.blur(input);
+        output.resize(input.width * Crt::SCALE, input.height * Crt::SCALE);
+        let width = output.width;
+
+        for y in 0..input.height {
+            for x in 0..input.width {
+                let (r, g, b) = unpack(input.pixels[y * input.width + x]);
+                let color = [r as u32, g as u32, b as u32];
+                let glow = self.glow[y * input.width + x];
+
+                for (row, row_weights) in self.weights.iter().enumerate() {
+                    let start = (y * Crt::SCALE + row) * width + x * Crt::SCALE;
+                    for (column, channels) in row_weights.iter().enumerate() {
+                        let [r, g, b] = [0, 1, 2].map(|channel| {
+                            let lit = (color[channel] * channels[channel]) >> 8;
+                            (lit + glow[channel] as u32).min(0xFF) as u8
+                        });
+                        output.pixels[start + column] = pack((r, g, b));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pack((r, g, b): (u8, u8, u8)) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+fn unpack(pixel: u32) -> (u8, u8, u8) {
+    ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+/// Rotates a neighborhood offset a quarter turn clockwise `turns` times, so one rule covers all four corners.
+fn rotate((dx, dy): (isize, isize), turns: usize) -> (isize, isize) {
+    (0..turns).fold((dx, dy), |(dx, dy), _| (-dy, dx))
+}
+
+/// A weighted average of colors.
+fn mix(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+    let channel = |shift: u32| {
+        let sum: u32 = colors
+            .iter()
+            .map(|(color, weight)| (color >> shift & 0xFF) * weight)
+            .sum();
+        sum / total
+    };
+    channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+/// hqx's test for two colors looking alike: within 48 of each other in brightness (Y), 7 in U and 6 in V.
+fn similar(a: u32, b: u32) -> bool {
+    if a == b {
+        return true;
+    }
+    let yuv = |color: u32| {
+        let (r, g, b) = unpack(color);
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        (
+            (r * 299 + g * 587 + b * 114) / 1000,
+            (-r * 169 - g * 331 + b * 500) / 1000,
+            (r * 500 - g * 419 - b * 81) / 1000,
+        )
+    };
+    let ((y1, u1, v1), (y2, u2, v2)) = (yuv(a), yuv(b));
+    (y1 - y2).abs() <= 48 && (u1 - u2).abs() <= 7 && (v1 - v2).abs() <= 6
+}
+
+/// The average of two colors.
+fn blend(a: u32, b: u32) -> u32 {
+    // Halve each channel before adding so carries can't cross into the next channel, then add back the bit both
+    // lost.
+    ((a >> 1) & 0x7F7F7F) + ((b >> 1) & 0x7F7F7F) + (a & b & 0x010101)
+}
+
+/// How different two colors look, weighting brightness above hue as xBR does.
+fn distance(a: u32, b: u32) -> u32 {
+    let (r1, g1, b1) = unpack(a);
+    let (r2, g2, b2) = unpack(b);
+    let (dr, dg, db) = (
+        r1 as i32 - r2 as i32,
+        g1 as i32 - g2 as i32,
+        b1 as i32 - b2 as i32,
+    );
+
+    let y = (dr * 299 + dg * 587 + db * 114) / 1000;
+    let u = (db - y) / 2;
+    let v = (dr - y) * 7 / 8;
+    48 * y.unsigned_abs() + 7 * u.unsigned_abs() + 6 * v.unsigned_abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0x000000;
+    const WHITE: u32 = 0xFFFFFF;
+
+    fn image(rows: &[&str]) -> Image {
+        let mut image = Image::new(rows[0].len(), rows.len());
+        for (pixel, c) in image.pixels.iter_mut().zip(rows.concat().chars()) {
+            *pixel = if c == '#' { WHITE } else { BLACK };
+        }
+        image
+    }
+
+    fn run(mut filter: impl Filter, input: &Image) -> Image {
+        let mut output = Image::new(0, 0);
+        filter.apply(input, &mut output);
+        output
+    }
+
+    #[test]
+    fn scale2x_rounds_diagonals() {
+        let output = run(Scale2x, &image(&["#.", ".#"]));
+        assert_eq!((output.width(), output.height()), (4, 4));
+        // Corners facing the diagonal take the color of the pixels either side of them, closing up the gap.
+        assert_eq!(output.pixels()[..4], [WHITE, WHITE, BLACK, BLACK]);
+        assert_eq!(output.pixels()[4..8], [WHITE, BLACK, WHITE, BLACK]);
+
+        let flat = run(Scale2x, &image(&["##", "##"]));
+        assert!(flat.pixels().iter().all(|pixel| *pixel == WHITE));
+    }
+
+    #[test]
+    fn scale3x_keeps_the_center() {
+        let input = image(&["#..", ".#.", "..#"]);
+        let output = run(Scale3x, &input);
+        assert_eq!((output.width(), output.height()), (9, 9));
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(
+                    output.pixels()[(y * 3 + 1) * 9 + x * 3 + 1],
+                    input.pixels()[y * 3 + x]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn xbr_blends_across_diagonal_edges() {
+        let input = image(&["#...", "##..", "###.", "####"]);
+        let output = run(Xbr2x, &input);
+        assert_eq!((output.width(), output.height()), (8, 8));
+        // The black pixel at (1, 0) sits on the staircase, so its bottom left corner blends towards the white below.
+        assert_eq!(output.pixels()[8 + 2], blend(BLACK, WHITE));
+        // Flat areas stay flat.
+        assert_eq!(output.pixels()[7], BLACK);
+        assert_eq!(output.pixels()[7 * 8], WHITE);
+    }
+
+    #[test]
+    fn hq2x_rounds_off_edges() {
+        let input = image(&["#...", "##..", "###.", "####"]);
+        let output = run(Hq2x, &input);
+        assert_eq!((output.width(), output.height()), (8, 8));
+        // The black pixel at (1, 0) has white below it and to its left, so its bottom left corner rounds off
+        // towards white, while its top right stays black.
+        assert_eq!(output.pixels()[8 + 2], mix(&[(BLACK, 2), (WHITE, 14)]));
+        assert_eq!(output.pixels()[3], BLACK);
+        // Flat areas stay flat.
+        assert_eq!(output.pixels()[7], BLACK);
+        assert_eq!(output.pixels()[7 * 8], WHITE);
+
+        let flat = run(Hq2x, &image(&["##", "##"]));
+        assert!(flat.pixels().iter().all(|pixel| *pixel == WHITE));
+    }
+
+    #[test]
+    fn hq3x_keeps_centers_and_rounds_corners() {
+        let input = image(&["#...", "##..", "###.", "####"]);
+        let output = run(Hq3x, &input);
+        assert_eq!((output.width(), output.height()), (12, 12));
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(
+                    output.pixels()[(y * 3 + 1) * 12 + x * 3 + 1],
+                    input.pixels()[y * 4 + x]
+                );
+            }
+        }
+        // Pixel (1, 0)'s bottom left corner rounds off towards white, and its bottom side picks up a little of it.
+        assert_eq!(output.pixels()[2 * 12 + 3], mix(&[(BLACK, 2), (WHITE, 14)]));
+        assert_eq!(output.pixels()[2 * 12 + 4], mix(&[(BLACK, 7), (WHITE, 1)]));
+        assert_eq!(output.pixels()[11], BLACK);
+
+        // Colors within the thresholds count as the same, so there's no edge for the sides to pick up.
+        let mut subtle = image(&["#.", ".#"]);
+        subtle.pixels = vec![0x101010, 0x141414, 0x141414, 0x101010];
+        let output = run(Hq3x, &subtle);
+        assert_eq!(output.pixels()[1], 0x101010);
+        assert_eq!(output.pixels()[6], 0x101010);
+    }
+
+    #[test]
+    fn crt_scanlines_mask_and_bloom() {
+        let input = image(&["....", "..#.", "....", "...."]);
+        let parameters = CrtParameters {
+            bloom: 0.0,
+            ..CrtParameters::default()
+        };
+        let output = run(Crt::new(parameters), &input);
+        assert_eq!((output.width(), output.height()), (12, 12));
+
+        let block =
+            |row: usize, column: usize| unpack(output.pixels()[(3 + row) * 12 + 6 + column]);
+        // The first column of a block favors red, the scanline gap is darker, and nothing glows without bloom.
+        let (r, g, _) = block(0, 0);
+        assert!(r > g);
+        assert!(block(2, 1).1 < block(0, 1).1);
+        assert_eq!(output.pixels()[0], BLACK);
+
+        let bloomed = run(Crt::new(CrtParameters::default()), &input);
+        assert_ne!(bloomed.pixels()[3 * 12 + 3], BLACK);
+    }
+
+    #[test]
+    fn chains_feed_each_filter_the_last_output() {
+        let mut frame = Frame::new();
+        frame.write(0, 0, 0x30);
+        let palette = Palette::ntsc();
+
+        let mut chain = FilterChain::new()
+            .then(Scale2x)
+            .then(Crt::new(CrtParameters::default()));
+        let output = chain.apply(&frame, &palette);
+        assert_eq!(output.width(), Frame::WIDTH * 2 * Crt::SCALE);
+        assert_eq!(output.height(), Frame::HEIGHT * 2 * Crt::SCALE);
+
+        let mut bytes = vec![0; output.width() * output.height() * 4];
+        output.convert(PixelFormat::Rgba8888, &mut bytes);
+        assert_eq!(bytes[3], 0xFF);
+    }
+}
@@ -1,4 +1,4 @@
-mod palettes;
+pub mod palettes;
 mod registers;
 mod rendering;
 
@@ -6,10 +6,12 @@ use crate::{
     cartridge::Cartridge,
     frame::Frame,
     memory::{PaletteRam, Ram},
+    region::Region,
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
 };
-use palettes::NTSC_PALETTE;
-use registers::{OamAddr, PpuAddr, PpuCtrl, PpuMask, PpuScroll, PpuStatus};
-use rendering::{BackgroundSlice, Sprite};
+use palettes::Palette;
+use registers::{IoLatch, OamAddr, PpuCtrl, PpuMask, PpuStatus, ScrollRegisters};
+use rendering::{BackgroundLatches, BackgroundShifter, Sprite, SpriteSlice};
 
 pub enum PpuRegister {
     PpuCtrl,
@@ -22,30 +24,39 @@ pub enum PpuRegister {
     PpuData,
 }
 
+#[derive(Clone)]
 pub struct PPU {
     ppu_ctrl: PpuCtrl,
     ppu_mask: PpuMask,
     ppu_status: PpuStatus,
     oam_addr: OamAddr,
-    ppu_scroll: PpuScroll,
-    ppu_addr: PpuAddr,
+    scroll: ScrollRegisters,
+    io_latch: IoLatch,
 
     oam: Ram<256>,
+    secondary_oam: Ram<32>,
     palette_ram: PaletteRam,
+    palette: Palette,
 
     ppu_data_read_buffer: u8,
 
     x: u16,
     y: u16,
-    current_background_slice: BackgroundSlice,
-    pub interrupt: bool,
+    background_latches: BackgroundLatches,
+    background_shifter: BackgroundShifter,
+    sprite_latch: u8,
+    sprite_slices: Vec<SpriteSlice>,
+    sprite_zero_on_next_line: bool,
+    sprite_zero_on_line: bool,
+    suppress_vblank: bool,
+    odd_frame: bool,
+    dots: u64,
+    region: Region,
 }
 
 impl PPU {
     const SCANLINE_LENGTH: u16 = 341;
-    const TOTAL_SCANLINES: u16 = 262;
     const VBLANK_START_SCANLINE: u16 = 240;
-    const NMI_SCANLINE: u16 = 241;
 
     pub fn new() -> Self {
         Self {
@@ -53,21 +64,55 @@ impl PPU {
             ppu_mask: PpuMask::new(),
             ppu_status: PpuStatus::new(),
             oam_addr: OamAddr::new(),
-            ppu_scroll: PpuScroll::new(),
-            ppu_addr: PpuAddr::new(),
+            scroll: ScrollRegisters::new(),
+            io_latch: IoLatch::new(),
 
             oam: Ram::<256>::new(),
+            secondary_oam: Ram::<32>::new(),
             palette_ram: PaletteRam::new(),
+            palette: Palette::ntsc(),
 
             ppu_data_read_buffer: 0,
 
             y: 0,
             x: 0,
-            current_background_slice: BackgroundSlice::new(0, 0, 0),
-            interrupt: false,
+            background_latches: BackgroundLatches::default(),
+            background_shifter: BackgroundShifter::default(),
+            sprite_latch: 0,
+            sprite_slices: Vec::with_capacity(8),
+            sprite_zero_on_next_line: false,
+            sprite_zero_on_line: false,
+            suppress_vblank: false,
+            odd_frame: false,
+            dots: 0,
+            region: Region::Ntsc,
         }
     }
 
+    /// What the reset button does to the PPU: the control, mask and scroll registers, the write toggle and the read
+    /// buffer are cleared, while OAM, palette RAM and the position in the frame are left alone.
+    pub fn reset(&mut self) {
+        self.ppu_ctrl = PpuCtrl::new();
+        self.ppu_mask = PpuMask::new();
+        self.scroll = ScrollRegisters::new();
+        self.ppu_data_read_buffer = 0;
+        self.odd_frame = false;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Switches the frame timing and emphasis behavior. There's no measured PAL palette bundled, so every region
+    /// starts out with the NTSC colors.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
     pub fn tick(&mut self, cartridge: &mut dyn Cartridge, frame: &mut Frame, cycles: u64) {
         for _ in 0..cycles {
             self.cycle(cartridge, frame);
@@ -75,122 +120,327 @@ impl PPU {
     }
 
     fn cycle(&mut self, cartridge: &mut dyn Cartridge, frame: &mut Frame) {
-        if self.x >= 257 && self.x <= 320 {
-            self.oam_addr.reset_latch();
-        }
+        self.dots += 1;
 
-        if self.x < 256 && self.y < 240 {
-            if self.x % 8 == 0 {
-                self.fetch_background_slice(cartridge);
+        if self.y == self.region.nmi_scanline() && self.x == 1 {
+            if !self.suppress_vblank {
+                self.ppu_status.set_vblank_started(true);
             }
+            self.suppress_vblank = false;
+        }
 
-            if self.ppu_mask.render_background() {
-                let color_index = self.current_background_slice.color(self.x % 8);
-                let palette_index = self.palette_ram[color_index];
-                let color = NTSC_PALETTE[palette_index as usize];
-                frame.write(self.x as usize, self.y as usize, color);
-            }
+        if self.y == self.region.pre_render_scanline() && self.x == 1 {
+            self.ppu_status.set_vblank_started(false);
+            self.ppu_status.set_sprite_zero_hit(false);
+            self.ppu_status.set_sprite_overflow(false);
+        }
 
-            if self.ppu_mask.render_sprites() {
-                let sprite_size = self.ppu_ctrl.sprite_size();
-                for (i, sprite) in self
-                    .oam
-                    .as_slice()
-                    .chunks_exact(4)
-                    .map(Sprite::new)
-                    .enumerate()
-                {
-                    if !sprite.contains_point(self.x as usize, self.y as usize, sprite_size) {
-                        continue;
-                    }
-                    frame.write(self.x as usize, self.y as usize, (0, 0, 255));
-                    break;
-                }
+        let rendering_enabled = self.ppu_mask.rendering_enabled();
+        let rendering_line = self.y < 240 || self.y == self.region.pre_render_scanline();
+
+        if rendering_enabled && rendering_line {
+            // Sprite tile fetches leave OAMADDR at 0.
+            if self.x >= 257 && self.x <= 320 {
+                self.oam_addr.reset_latch();
             }
+            self.shift_background();
+        }
+
+        // Each dot is 8 of the 12 subcarrier phases long.
+        if self.y == 0 && self.x == 1 {
+            frame.set_phase((self.dots * 8 % 12) as u8);
+        }
 
-            if !self.ppu_mask.rendering_enabled() {
-                frame.write(self.x as usize, self.y as usize, (0, 0, 0));
+        if self.y < 240 && self.x >= 1 && self.x <= 256 {
+            let x = self.x - 1;
+            if rendering_enabled {
+                let background_visible = self.ppu_mask.render_background()
+                    && (x >= 8 || self.ppu_mask.render_background_in_left_margin());
+                let background_color = match background_visible {
+                    true => self.background_shifter.color(self.scroll.fine_x()),
+                    false => 0,
+                };
+                let palette_index = self.compose_pixel(x, background_color);
+                let color_index = self.output_color_index(self.palette_ram[palette_index]);
+                frame.write(x as usize, self.y as usize, color_index);
+            } else {
+                frame.write(x as usize, self.y as usize, 0x0F);
             }
         }
 
+        if rendering_enabled && rendering_line {
+            self.fetch(cartridge);
+            self.update_scroll();
+        }
+
         self.x += 1;
-        if self.x >= PPU::SCANLINE_LENGTH {
+
+        // With rendering enabled, the last dot of the pre-render line is skipped on odd frames.
+        let skip_dot = self.odd_frame
+            && rendering_enabled
+            && self.region.skips_odd_frame_dot()
+            && self.y == self.region.pre_render_scanline();
+        if self.x >= PPU::SCANLINE_LENGTH || (skip_dot && self.x == PPU::SCANLINE_LENGTH - 1) {
             self.x = 0;
             self.y += 1;
 
-            if self.y == PPU::NMI_SCANLINE {
-                if self.ppu_ctrl.nmi_enabled() {
-                    self.interrupt = true;
-                }
-                self.ppu_status.set_vblank_started(true);
-                self.ppu_status.set_sprite_zero_hit(false);
+            if self.y >= self.region.scanlines() {
+                self.y = 0;
+                self.odd_frame = !self.odd_frame;
             }
+        }
+    }
 
-            if self.y >= PPU::TOTAL_SCANLINES {
-                self.y = 0;
-                self.interrupt = false;
-                self.ppu_status.set_vblank_started(false);
-                self.ppu_status.set_sprite_zero_hit(false);
+    /// The state of the PPU's /NMI output: high while the vblank flag and NMI generation are both on. The CPU
+    /// triggers on its rising edge, so enabling NMI during vblank causes an immediate NMI.
+    pub fn nmi_line(&self) -> bool {
+        self.ppu_status.vblank_started() && self.ppu_ctrl.nmi_enabled()
+    }
+
+    /// Shifts the background shift registers and reloads them from the fetch latches every eighth dot.
+    fn shift_background(&mut self) {
+        if (2..=257).contains(&self.x) || (322..=337).contains(&self.x) {
+            self.background_shifter.shift();
+
+            if self.x % 8 == 1 {
+                self.background_shifter.reload(
+                    self.background_latches.lower_bit_plane,
+                    self.background_latches.upper_bit_plane,
+                    self.background_latches.palette_section,
+                );
             }
         }
     }
 
-    fn fetch_background_slice(&mut self, cartridge: &mut dyn Cartridge) {
-        let tile_x = (self.x / 8) as u16;
-        let tile_y = (self.y / 8) as u16;
-        let fine_y = (self.y % 8) as u16;
+    /// Performs the memory access the PPU makes on the current dot of a rendering scanline. Each access occupies two
+    /// dots; the read is modelled on the first of them.
+    fn fetch(&mut self, cartridge: &mut dyn Cartridge) {
+        match self.x {
+            1..=256 | 321..=336 => self.fetch_background(cartridge),
+            257..=320 => self.fetch_sprites(cartridge),
+            // Unused nametable fetches at the end of the line.
+            337 | 339 => {
+                cartridge.ppu_read(self.scroll.current().tile_address());
+            }
+            _ => (),
+        }
+    }
 
-        let nametable_address = self.ppu_ctrl.nametable_base_address();
-        let nametable_offset = tile_y * 32 + tile_x;
-        let nametable_entry = cartridge.ppu_read(nametable_address + nametable_offset);
+    fn fetch_background(&mut self, cartridge: &mut dyn Cartridge) {
+        let v = self.scroll.current();
+        let latches = &mut self.background_latches;
+
+        match (self.x - 1) % 8 {
+            0 => latches.tile_index = cartridge.ppu_read(v.tile_address()),
+            2 => {
+                let attribute_byte = cartridge.ppu_read(v.attribute_address());
+                latches.palette_section = (attribute_byte >> v.attribute_shift()) & 0x03;
+            }
+            4 => {
+                let address = self.background_pattern_address(v.fine_y());
+                self.background_latches.lower_bit_plane = cartridge.ppu_read(address);
+            }
+            6 => {
+                let address = self.background_pattern_address(v.fine_y());
+                self.background_latches.upper_bit_plane = cartridge.ppu_read(address + 8);
+            }
+            7 => self.scroll.current_mut().increment_coarse_x(),
+            _ => (),
+        }
+    }
 
+    fn background_pattern_address(&self, fine_y: u16) -> u16 {
         let pattern_table_address = self.ppu_ctrl.background_pattern_table_address();
-        let pattern_slice_offset = (nametable_entry as u16) << 4 | fine_y;
-        let lower_bit_plane = cartridge.ppu_read(pattern_table_address + pattern_slice_offset);
-        let upper_bit_plane = cartridge.ppu_read(pattern_table_address + pattern_slice_offset + 8);
+        pattern_table_address | (self.background_latches.tile_index as u16) << 4 | fine_y
+    }
 
-        let attribute_table_address = nametable_address + 0x3C0;
-        let attribute_table_offset = (tile_y / 4) * 8 + (tile_x / 4);
-        let attribute_byte = cartridge.ppu_read(attribute_table_address + attribute_table_offset);
+    /// Fetches the pattern data for the sprites found by sprite evaluation, one sprite every eight dots. Empty slots
+    /// still fetch (tile $FF), which mappers watching the PPU address bus rely on.
+    fn fetch_sprites(&mut self, cartridge: &mut dyn Cartridge) {
+        if self.x == 257 {
+            self.evaluate_sprites();
+            self.sprite_slices.clear();
+            self.sprite_zero_on_line = self.sprite_zero_on_next_line;
+        }
+
+        let slot = (self.x as usize - 257) / 8;
+        let sprite = Sprite::new(&self.secondary_oam.as_slice()[slot * 4..slot * 4 + 4]);
+        let sprite_size = self.ppu_ctrl.sprite_size();
+        let row = sprite.row_on_scanline(self.y, sprite_size);
+        let pattern_table_address = self.ppu_ctrl.sprite_pattern_table_address_for_8x8();
+        let address = sprite.pattern_address(row.unwrap_or(0), sprite_size, pattern_table_address);
 
-        let tile_quadrant = ((tile_y / 2) % 2) << 1 | (tile_x / 2) % 2;
-        let palette_section = (attribute_byte >> (tile_quadrant * 2)) & 0x03;
+        match (self.x - 257) % 8 {
+            // The sprite fetches are preceded by two unused nametable fetches.
+            0 | 2 => {
+                cartridge.ppu_read(self.scroll.current().tile_address());
+            }
+            4 => self.sprite_latch = cartridge.ppu_read(address),
+            6 => {
+                let upper_bit_plane = cartridge.ppu_read(address + 8);
+                if row.is_some() && self.y != self.region.pre_render_scanline() {
+                    let slice = SpriteSlice::new(&sprite, self.sprite_latch, upper_bit_plane);
+                    self.sprite_slices.push(slice);
+                }
+            }
+            _ => (),
+        }
+    }
 
-        self.current_background_slice =
-            BackgroundSlice::new(lower_bit_plane, upper_bit_plane, palette_section);
+    /// Applies grayscale and color emphasis from PPUMASK to a palette RAM value, giving the PPU's 9-bit output color.
+    fn output_color_index(&self, palette_value: u8) -> u16 {
+        let grayscale_mask = match self.ppu_mask.grayscale() {
+            true => 0x30,
+            false => 0x3F,
+        };
+        let emphasis = match self.region.swaps_red_green_emphasis() {
+            true => {
+                let emphasis = self.ppu_mask.emphasis();
+                (emphasis & 0x04) | (emphasis & 0x01) << 1 | (emphasis & 0x02) >> 1
+            }
+            false => self.ppu_mask.emphasis(),
+        };
+        emphasis << 6 | (palette_value & grayscale_mask) as u16
     }
 
-    pub fn in_vblank(&self) -> bool {
-        self.y >= PPU::VBLANK_START_SCANLINE
+    /// Picks between the background and the first opaque sprite pixel at the current dot and returns the palette RAM
+    /// index of the result. Also detects sprite zero hits.
+    fn compose_pixel(&mut self, x: u16, background_color: u16) -> u16 {
+        let sprites_visible = self.ppu_mask.render_sprites()
+            && (x >= 8 || self.ppu_mask.render_sprites_in_left_margin());
+        let sprite = match sprites_visible {
+            true => self
+                .sprite_slices
+                .iter()
+                .enumerate()
+                .map(|(i, slice)| (i, slice.color(x), slice.above_background()))
+                .find(|(_, color, _)| *color != 0),
+            false => None,
+        };
+
+        if let Some((i, _, _)) = sprite {
+            if i == 0 && self.sprite_zero_on_line && background_color != 0 {
+                self.detect_sprite_zero_hit(x);
+            }
+        }
+
+        match sprite {
+            Some((_, sprite_color, above_background))
+                if background_color == 0 || above_background =>
+            {
+                sprite_color
+            }
+            _ => background_color,
+        }
     }
 
-    pub fn oam_dma(&mut self, oam_data: &[u8; 256]) {
-        self.oam.copy_from_slice(oam_data)
+    fn detect_sprite_zero_hit(&mut self, x: u16) {
+        // Only opaque pixels of both layers count, which the caller has checked. Hits can't happen at x=255, or in the
+        // left margin when either layer is clipped there.
+        let left_margin_clipped = !self.ppu_mask.render_background_in_left_margin()
+            || !self.ppu_mask.render_sprites_in_left_margin();
+        if x == 255 || (x < 8 && left_margin_clipped) {
+            return;
+        }
+        self.ppu_status.set_sprite_zero_hit(true);
+    }
+
+    /// Finds the first eight sprites in OAM that cover the current scanline and copies them into secondary OAM. They
+    /// are drawn on the next scanline.
+    fn evaluate_sprites(&mut self) {
+        for i in 0..32 {
+            self.secondary_oam[i] = 0xFF;
+        }
+        self.sprite_zero_on_next_line = false;
+
+        // Nothing is drawn on the first visible line, so the pre-render line never finds any sprites.
+        if self.y == self.region.pre_render_scanline() {
+            return;
+        }
+
+        let sprite_size = self.ppu_ctrl.sprite_size();
+        let in_range = |y: u8| (self.y.wrapping_sub(y as u16)) < Sprite::height(sprite_size);
+
+        let mut found: u16 = 0;
+        let mut n: u16 = 0;
+        while n < 64 && found < 8 {
+            if in_range(self.oam[n * 4]) {
+                for i in 0..4 {
+                    self.secondary_oam[found * 4 + i] = self.oam[n * 4 + i];
+                }
+                self.sprite_zero_on_next_line |= n == 0;
+                found += 1;
+            }
+            n += 1;
+        }
+
+        // Once secondary OAM is full, the hardware keeps looking for a ninth sprite to set the overflow flag. Due to a
+        // bug it increments the byte index along with the sprite index, so it checks tile numbers, attributes and X
+        // positions as if they were Y coordinates.
+        let mut m: u16 = 0;
+        while n < 64 {
+            if in_range(self.oam[n * 4 + m]) {
+                self.ppu_status.set_sprite_overflow(true);
+                break;
+            }
+            n += 1;
+            m = (m + 1) % 4;
+        }
+    }
+
+    fn update_scroll(&mut self) {
+        match self.x {
+            256 => self.scroll.current_mut().increment_y(),
+            257 => self.scroll.copy_horizontal(),
+            280..=304 if self.y == self.region.pre_render_scanline() => self.scroll.copy_vertical(),
+            _ => (),
+        }
+    }
+
+    /// Whether the PPU is currently fetching from VRAM and OAM, which changes how $2004 and $2007 behave.
+    fn rendering_active(&self) -> bool {
+        self.ppu_mask.rendering_enabled()
+            && (self.y < 240 || self.y == self.region.pre_render_scanline())
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        self.oam.as_slice()
+    }
+
+    /// Reads PPU memory without side effects, for observers and debuggers.
+    pub fn peek_memory(&self, cartridge: &dyn Cartridge, address: u16) -> u8 {
+        match address & 0x3FFF {
+            address @ 0..=0x2FFF => cartridge.ppu_peek(address),
+            address @ 0x3000..=0x3EFF => cartridge.ppu_peek(address - 0x1000),
+            address => self.palette_ram[address - 0x3F00],
+        }
+    }
+
+    /// The scanline and dot being drawn.
+    pub fn position(&self) -> (u16, u16) {
+        (self.y, self.x)
+    }
+
+    pub fn in_vblank(&self) -> bool {
+        self.y >= PPU::VBLANK_START_SCANLINE
     }
 
     pub fn peek_register(&self, register: PpuRegister) -> u8 {
         match register {
-            PpuRegister::PpuCtrl => 0,
-            PpuRegister::PpuMask => 0,
             PpuRegister::PpuStatus => self.peek_ppu_status(),
-            PpuRegister::OamAddr => 0,
             PpuRegister::OamData => self.peek_oam_data(),
-            PpuRegister::PpuScroll => 0,
-            PpuRegister::PpuAddr => 0,
             PpuRegister::PpuData => self.peek_ppu_data(),
+            // Write-only registers read back whatever is left on the bus.
+            _ => self.io_latch.peek(self.dots),
         }
     }
 
     pub fn read_register(&mut self, cartridge: &mut dyn Cartridge, register: PpuRegister) -> u8 {
         match register {
-            PpuRegister::PpuCtrl => 0,
-            PpuRegister::PpuMask => 0,
             PpuRegister::PpuStatus => self.read_ppu_status(),
-            PpuRegister::OamAddr => 0,
             PpuRegister::OamData => self.read_oam_data(),
-            PpuRegister::PpuScroll => 0,
-            PpuRegister::PpuAddr => 0,
             PpuRegister::PpuData => self.read_ppu_data(cartridge),
+            _ => self.io_latch.read(self.dots),
         }
     }
 
@@ -200,6 +450,7 @@ impl PPU {
         register: PpuRegister,
         value: u8,
     ) {
+        self.io_latch.refresh(value, 0xFF, self.dots);
         match register {
             PpuRegister::PpuCtrl => self.write_ppu_ctrl(value),
             PpuRegister::PpuMask => self.write_ppu_mask(value),
@@ -215,6 +466,7 @@ impl PPU {
     // PPU_CTRL ($2000 > write)
     fn write_ppu_ctrl(&mut self, value: u8) {
         self.ppu_ctrl.write(value);
+        self.scroll.write_ppu_ctrl(value);
     }
 
     // PPU_MASK ($2001 > write)
@@ -224,14 +476,21 @@ impl PPU {
 
     // PPU_STATUS ($2002 < read)
     fn peek_ppu_status(&self) -> u8 {
-        self.ppu_status.bits()
+        // Only the top three bits are driven; the rest come from the I/O latch.
+        (self.ppu_status.bits() & 0xE0) | (self.io_latch.peek(self.dots) & 0x1F)
     }
 
     fn read_ppu_status(&mut self) -> u8 {
-        let value = self.ppu_status.read();
-        self.ppu_addr.reset_latch();
-        self.ppu_scroll.reset_latch();
-        value
+        // Reading one dot before the vblank flag is set returns it clear and keeps it (and the NMI) from being set this
+        // frame. Reads just after it's set return it and clear it before the NMI can be noticed.
+        if self.y == self.region.nmi_scanline() && self.x == 1 {
+            self.suppress_vblank = true;
+        }
+
+        let status = self.ppu_status.read();
+        self.io_latch.refresh(status, 0xE0, self.dots);
+        self.scroll.reset_latch();
+        self.io_latch.read(self.dots)
     }
 
     // OAM_ADDR ($2003 > write)
@@ -241,61 +500,109 @@ impl PPU {
 
     // OAM_DATA ($2004 <> read/write)
     fn peek_oam_data(&self) -> u8 {
-        self.oam[self.oam_addr.bits()]
+        // While rendering, reads see whatever sprite evaluation and the sprite fetches have on OAM's data bus.
+        if self.rendering_active() {
+            return match self.x {
+                1..=64 => 0xFF,
+                65..=256 => self.oam[self.oam_addr.bits()],
+                257..=320 => {
+                    let slot = (self.x - 257) / 8;
+                    let byte = ((self.x - 257) % 8).min(3);
+                    self.secondary_oam[slot * 4 + byte]
+                }
+                _ => self.secondary_oam[0],
+            };
+        }
+
+        let address = self.oam_addr.bits();
+        // Bits 2-4 of sprite attributes don't exist in OAM and always read back as 0.
+        if address % 4 == 2 {
+            self.oam[address] & 0xE3
+        } else {
+            self.oam[address]
+        }
     }
 
     fn read_oam_data(&mut self) -> u8 {
-        let oam_data = self.oam[self.oam_addr.bits()];
-        if !self.in_vblank() {
-            self.oam_addr.increment();
-        }
+        // Reads don't increment OAMADDR.
+        let oam_data = self.peek_oam_data();
+        self.io_latch.refresh(oam_data, 0xFF, self.dots);
         oam_data
     }
 
     fn write_oam_data(&mut self, value: u8) {
+        // Writes during rendering are dropped, but bump OAMADDR to the next sprite like sprite evaluation does.
+        if self.rendering_active() {
+            self.oam_addr.increment_sprite();
+            return;
+        }
+
         self.oam[self.oam_addr.bits()] = value;
         self.oam_addr.increment();
     }
 
     // PPU_SCROLL ($2005 >> write x2)
     fn write_ppu_scroll(&mut self, value: u8) {
-        self.ppu_scroll.write(value);
+        self.scroll.write_ppu_scroll(value);
     }
 
     // PPU_ADDR ($2006 >> write x2)
     fn write_ppu_addr(&mut self, value: u8) {
-        self.ppu_addr.write(value);
+        self.scroll.write_ppu_addr(value);
     }
 
     // PPU_DATA ($2007 <> read/write)
     fn peek_ppu_data(&self) -> u8 {
-        let address: u16 = self.ppu_addr.bits();
+        let address: u16 = self.scroll.address();
         match address {
             0..=0x3EFF => self.ppu_data_read_buffer,
-            0x3F00..=0x3FFF => self.palette_ram[address - 0x3F00],
+            0x3F00..=0x3FFF => self.peek_palette_data(address),
             _ => unreachable!(),
         }
     }
 
+    /// Palette entries are 6 bits wide, so the top two bits of a palette read come from the I/O latch.
+    fn peek_palette_data(&self, address: u16) -> u8 {
+        (self.palette_ram[address - 0x3F00] & 0x3F) | (self.io_latch.peek(self.dots) & 0xC0)
+    }
+
+    /// Steps v after a $2007 access. During rendering the PPU's own increment logic is triggered instead: a coarse X
+    /// increment and a Y increment at the same time.
+    fn increment_vram_address(&mut self) {
+        if self.rendering_active() {
+            self.scroll.current_mut().increment_coarse_x();
+            self.scroll.current_mut().increment_y();
+        } else {
+            let increment = self.ppu_ctrl.vram_address_increment();
+            self.scroll.current_mut().increment(increment);
+        }
+    }
+
     fn read_ppu_data(&mut self, cartridge: &mut dyn Cartridge) -> u8 {
-        let address: u16 = self.ppu_addr.bits();
-        let increment = self.ppu_ctrl.vram_address_increment();
-        self.ppu_addr.increment(increment);
+        let address: u16 = self.scroll.address();
+        self.increment_vram_address();
 
         match address {
             0..=0x3EFF => {
                 let buffered_read = cartridge.ppu_read(address);
-                std::mem::replace(&mut self.ppu_data_read_buffer, buffered_read)
+                let value = std::mem::replace(&mut self.ppu_data_read_buffer, buffered_read);
+                self.io_latch.refresh(value, 0xFF, self.dots);
+                value
+            }
+            0x3F00..=0x3FFF => {
+                // Palette reads are immediate, but the buffer still picks up the nametable byte "underneath" them.
+                self.ppu_data_read_buffer = cartridge.ppu_read(address - 0x1000);
+                let value = self.peek_palette_data(address);
+                self.io_latch.refresh(value, 0x3F, self.dots);
+                value
             }
-            0x3F00..=0x3FFF => self.palette_ram[address - 0x3F00],
             _ => unreachable!(),
         }
     }
 
     fn write_ppu_data(&mut self, cartridge: &mut dyn Cartridge, value: u8) {
-        let address: u16 = self.ppu_addr.bits();
-        let increment = self.ppu_ctrl.vram_address_increment();
-        self.ppu_addr.increment(increment);
+        let address: u16 = self.scroll.address();
+        self.increment_vram_address();
 
         match address {
             0..=0x3EFF => cartridge.ppu_write(address, value),
@@ -304,3 +611,560 @@ impl PPU {
         }
     }
 }
+
+/// The palette and region are settings rather than state, so they stay as they are when a state is loaded.
+impl Snapshot for PPU {
+    fn save(&self, writer: &mut StateWriter) {
+        self.ppu_ctrl.save(writer);
+        self.ppu_mask.save(writer);
+        self.ppu_status.save(writer);
+        self.oam_addr.save(writer);
+        self.scroll.save(writer);
+        self.io_latch.save(writer);
+
+        self.oam.save(writer);
+        self.secondary_oam.save(writer);
+        self.palette_ram.save(writer);
+
+        writer.write_u8(self.ppu_data_read_buffer);
+
+        writer.write_u16(self.x);
+        writer.write_u16(self.y);
+        self.background_latches.save(writer);
+        self.background_shifter.save(writer);
+        writer.write_u8(self.sprite_latch);
+        writer.write_u8(self.sprite_slices.len() as u8);
+        for sprite_slice in &self.sprite_slices {
+            sprite_slice.save(writer);
+        }
+        writer.write_bool(self.sprite_zero_on_next_line);
+        writer.write_bool(self.sprite_zero_on_line);
+        writer.write_bool(self.suppress_vblank);
+        writer.write_bool(self.odd_frame);
+        writer.write_u64(self.dots);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.ppu_ctrl.load(reader)?;
+        self.ppu_mask.load(reader)?;
+        self.ppu_status.load(reader)?;
+        self.oam_addr.load(reader)?;
+        self.scroll.load(reader)?;
+        self.io_latch.load(reader)?;
+
+        self.oam.load(reader)?;
+        self.secondary_oam.load(reader)?;
+        self.palette_ram.load(reader)?;
+
+        self.ppu_data_read_buffer = reader.read_u8()?;
+
+        self.x = reader.read_u16()?;
+        self.y = reader.read_u16()?;
+        if self.x >= PPU::SCANLINE_LENGTH || self.y >= self.region.scanlines() {
+            return Err(SaveStateError::Malformed);
+        }
+        self.background_latches.load(reader)?;
+        self.background_shifter.load(reader)?;
+        self.sprite_latch = reader.read_u8()?;
+        let sprite_slices = reader.read_u8()?;
+        if sprite_slices > 8 {
+            return Err(SaveStateError::Malformed);
+        }
+        self.sprite_slices.clear();
+        for _ in 0..sprite_slices {
+            self.sprite_slices.push(SpriteSlice::load(reader)?);
+        }
+        self.sprite_zero_on_next_line = reader.read_bool()?;
+        self.sprite_zero_on_line = reader.read_bool()?;
+        self.suppress_vblank = reader.read_bool()?;
+        self.odd_frame = reader.read_bool()?;
+        self.dots = reader.read_u64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cartridge with 8 KB of CHR RAM and 4 KB of nametable RAM (four-screen), and nothing on the CPU side.
+    #[derive(Clone)]
+    struct TestCartridge {
+        ppu_memory: Vec<u8>,
+        ppu_reads: Vec<u16>,
+    }
+
+    impl TestCartridge {
+        fn new() -> Self {
+            Self {
+                ppu_memory: vec![0; 0x3000],
+                ppu_reads: Vec::new(),
+            }
+        }
+    }
+
+    #[allow(unused_variables)]
+    impl Cartridge for TestCartridge {
+        fn cpu_peek(&self, address: u16) -> u8 {
+            0
+        }
+
+        fn cpu_read(&mut self, address: u16) -> u8 {
+            0
+        }
+
+        fn cpu_write(&mut self, address: u16, value: u8) {}
+
+        fn ppu_peek(&self, address: u16) -> u8 {
+            self.ppu_memory[address as usize]
+        }
+
+        fn ppu_read(&mut self, address: u16) -> u8 {
+            self.ppu_reads.push(address);
+            self.ppu_memory[address as usize]
+        }
+
+        fn ppu_write(&mut self, address: u16, value: u8) {
+            self.ppu_memory[address as usize] = value;
+        }
+
+        fn rom_hash(&self) -> u64 {
+            0
+        }
+
+        fn box_clone(&self) -> Box<dyn Cartridge> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Sets up a frame where every background tile and sprite uses tile 1, a solid block of color 1.
+    fn solid_tile_setup() -> (PPU, TestCartridge, Frame) {
+        let mut cartridge = TestCartridge::new();
+        for row in 0..8 {
+            cartridge.ppu_memory[0x0010 + row] = 0xFF;
+        }
+        for i in 0..0x3C0 {
+            cartridge.ppu_memory[0x2000 + i] = 0x01;
+        }
+
+        let mut ppu = PPU::new();
+        for i in 0..256 {
+            ppu.oam[i] = 0xFF;
+        }
+        // Show both layers everywhere, including the left margin.
+        ppu.write_ppu_mask(0x1E);
+        (ppu, cartridge, Frame::new())
+    }
+
+    fn place_sprite(ppu: &mut PPU, index: u16, x: u8, y: u8) {
+        ppu.oam[index * 4] = y;
+        ppu.oam[index * 4 + 1] = 0x01;
+        ppu.oam[index * 4 + 2] = 0x00;
+        ppu.oam[index * 4 + 3] = x;
+    }
+
+    fn run_until_sprite_zero_hit(
+        ppu: &mut PPU,
+        cartridge: &mut TestCartridge,
+        frame: &mut Frame,
+    ) -> Option<(u16, u16)> {
+        for _ in 0..(PPU::SCANLINE_LENGTH as u64 * 240) {
+            ppu.tick(cartridge, frame, 1);
+            if ppu.ppu_status.bits() & 0x40 != 0 {
+                return Some((ppu.x - 1, ppu.y));
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn sprite_zero_hit_timing() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        place_sprite(&mut ppu, 0, 40, 30);
+
+        // Pixel x is output on dot x + 1.
+        let hit = run_until_sprite_zero_hit(&mut ppu, &mut cartridge, &mut frame);
+        assert_eq!(hit, Some((41, 31)));
+    }
+
+    #[test]
+    fn no_sprite_zero_hit_at_x_255_or_when_clipped() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        place_sprite(&mut ppu, 0, 255, 30);
+        assert_eq!(
+            run_until_sprite_zero_hit(&mut ppu, &mut cartridge, &mut frame),
+            None
+        );
+
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        place_sprite(&mut ppu, 0, 4, 30);
+        ppu.write_ppu_mask(0x1A);
+        let hit = run_until_sprite_zero_hit(&mut ppu, &mut cartridge, &mut frame);
+        assert_eq!(hit, Some((9, 31)));
+    }
+
+    #[test]
+    fn sprite_overflow() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        for i in 0..8 {
+            place_sprite(&mut ppu, i, 0, 100);
+        }
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            PPU::SCANLINE_LENGTH as u64 * 240,
+        );
+        assert_eq!(ppu.ppu_status.bits() & 0x20, 0);
+
+        place_sprite(&mut ppu, 8, 0, 100);
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            PPU::SCANLINE_LENGTH as u64 * 262,
+        );
+        assert_ne!(ppu.ppu_status.bits() & 0x20, 0);
+    }
+
+    #[test]
+    fn sprite_overflow_hardware_bug() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        for i in 0..8 {
+            place_sprite(&mut ppu, i, 0, 100);
+        }
+        // Sprite 8's Y is out of range, so the buggy scan checks sprite 9's tile number instead of its Y coordinate.
+        place_sprite(&mut ppu, 9, 0, 0);
+        ppu.oam[9 * 4 + 1] = 100;
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            PPU::SCANLINE_LENGTH as u64 * 240,
+        );
+        assert_ne!(ppu.ppu_status.bits() & 0x20, 0);
+    }
+
+    #[test]
+    fn fetch_cadence() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        place_sprite(&mut ppu, 0, 0, 0);
+        ppu.write_ppu_ctrl(0x08);
+        ppu.tick(&mut cartridge, &mut frame, PPU::SCANLINE_LENGTH as u64);
+
+        let mut reads = Vec::new();
+        for dot in 0..PPU::SCANLINE_LENGTH {
+            cartridge.ppu_reads.clear();
+            ppu.tick(&mut cartridge, &mut frame, 1);
+            if let [address] = cartridge.ppu_reads[..] {
+                reads.push((dot, address));
+            } else {
+                assert!(cartridge.ppu_reads.is_empty());
+            }
+        }
+
+        // Every odd dot except 341, which doesn't exist.
+        assert_eq!(reads.len(), 170);
+        assert!(reads.iter().all(|(dot, _)| dot % 2 == 1));
+
+        // Nametable, attribute, pattern low and pattern high for the third tile of the line (fine Y = 1).
+        let tile: Vec<u16> = reads[0..4].iter().map(|(_, address)| *address).collect();
+        assert_eq!(tile, [0x2002, 0x23C0, 0x0011, 0x0019]);
+
+        // Sprite 0 is on this line; the other seven slots fetch tile $FF from the table selected by PPUCTRL.
+        let sprite_reads: Vec<u16> = reads
+            .iter()
+            .filter(|(dot, _)| (257..=320).contains(dot) && (dot - 257) % 8 >= 4)
+            .map(|(_, address)| *address)
+            .collect();
+        assert_eq!(sprite_reads[0..2], [0x1011, 0x1019]);
+        assert!(sprite_reads[2..]
+            .iter()
+            .all(|address| address & 0x1FF0 == 0x1FF0));
+    }
+
+    #[test]
+    fn grayscale_emphasis_and_left_margin() {
+        let pixel = |frame: &Frame, x: usize, y: usize| frame.color_indices()[y * Frame::WIDTH + x];
+
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        ppu.palette_ram[0x00] = 0x0F;
+        ppu.palette_ram[0x01] = 0x16;
+        // Hide the background in the left margin, emphasize red and turn on grayscale.
+        ppu.write_ppu_mask(0x3D);
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            PPU::SCANLINE_LENGTH as u64 * 262,
+        );
+
+        // $0F (backdrop) and $16 both become their column's gray, with red emphasis.
+        assert_eq!(pixel(&frame, 7, 10), 0x40);
+        assert_eq!(pixel(&frame, 8, 10), 0x50);
+        assert_ne!(ppu.palette.color(0x40 | 0x10), ppu.palette.color(0x10));
+    }
+
+    /// Ticks a fresh PPU with rendering off to the given dot, so no cartridge memory is touched.
+    fn ppu_at(scanline: u16, dot: u16) -> (PPU, TestCartridge, Frame) {
+        let (mut cartridge, mut frame) = (TestCartridge::new(), Frame::new());
+        let mut ppu = PPU::new();
+        let dots = scanline as u64 * PPU::SCANLINE_LENGTH as u64 + dot as u64;
+        ppu.tick(&mut cartridge, &mut frame, dots);
+        (ppu, cartridge, frame)
+    }
+
+    #[test]
+    fn vblank_flag_timing() {
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(241, 1);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+        ppu.tick(&mut cartridge, &mut frame, 1);
+        assert_ne!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+
+        ppu.tick(&mut cartridge, &mut frame, 19 * PPU::SCANLINE_LENGTH as u64);
+        assert_eq!((ppu.y, ppu.x), (260, 2));
+        assert_ne!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+        ppu.tick(&mut cartridge, &mut frame, PPU::SCANLINE_LENGTH as u64);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+    }
+
+    #[test]
+    fn reading_status_as_vblank_starts_suppresses_it() {
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(241, 1);
+        ppu.write_ppu_ctrl(0x80);
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuStatus) & 0x80,
+            0
+        );
+        ppu.tick(&mut cartridge, &mut frame, 10);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+        assert!(!ppu.nmi_line());
+
+        // Reading it afterwards returns it set and clears it, which drops the NMI line.
+        let (mut ppu, mut cartridge, _) = ppu_at(241, 2);
+        ppu.write_ppu_ctrl(0x80);
+        assert!(ppu.nmi_line());
+        assert_ne!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuStatus) & 0x80,
+            0
+        );
+        assert!(!ppu.nmi_line());
+    }
+
+    #[test]
+    fn status_reads_racing_vblank_start() {
+        // For a $2002 read at each dot around the flag being set: what the read returns, whether the flag is still
+        // set afterwards or gets set later, and whether the NMI line is ever raised after the read.
+        let race = |dot: u16| {
+            let (mut ppu, mut cartridge, mut frame) = ppu_at(241, dot);
+            ppu.write_ppu_ctrl(0x80);
+            let read = ppu.read_register(&mut cartridge, PpuRegister::PpuStatus) & 0x80 != 0;
+            let mut nmi = ppu.nmi_line();
+            for _ in 0..10 {
+                ppu.tick(&mut cartridge, &mut frame, 1);
+                nmi |= ppu.nmi_line();
+            }
+            let flag = ppu.peek_register(PpuRegister::PpuStatus) & 0x80 != 0;
+            (read, flag, nmi)
+        };
+
+        // Two dots early, the read misses the flag and vblank goes ahead as normal.
+        assert_eq!(race(0), (false, true, true));
+        // One dot early, the read misses the flag and keeps it, and the NMI, from happening this frame.
+        assert_eq!(race(1), (false, false, false));
+        // Just after it's set, the read sees it and clears it, taking the NMI line back down.
+        assert_eq!(race(2), (true, false, false));
+        assert_eq!(race(3), (true, false, false));
+
+        // The suppression only lasts the one frame.
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(241, 1);
+        ppu.read_register(&mut cartridge, PpuRegister::PpuStatus);
+        let frame_length = PPU::SCANLINE_LENGTH as u64 * Region::Ntsc.scanlines() as u64;
+        ppu.tick(&mut cartridge, &mut frame, frame_length + 1);
+        assert_ne!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+    }
+
+    #[test]
+    fn enabling_nmi_during_vblank_raises_nmi_line() {
+        let (mut ppu, _, _) = ppu_at(250, 0);
+        assert!(!ppu.nmi_line());
+        ppu.write_ppu_ctrl(0x80);
+        assert!(ppu.nmi_line());
+        ppu.write_ppu_ctrl(0x00);
+        assert!(!ppu.nmi_line());
+    }
+
+    #[test]
+    fn odd_frames_skip_a_dot_when_rendering() {
+        let frame_length = PPU::SCANLINE_LENGTH as u64 * Region::Ntsc.scanlines() as u64;
+
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        ppu.tick(&mut cartridge, &mut frame, frame_length);
+        assert_eq!((ppu.y, ppu.x), (0, 0));
+        ppu.tick(&mut cartridge, &mut frame, frame_length - 1);
+        assert_eq!((ppu.y, ppu.x), (0, 0));
+
+        // With rendering off, every frame is the full length.
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(0, 0);
+        ppu.tick(&mut cartridge, &mut frame, frame_length * 2);
+        assert_eq!((ppu.y, ppu.x), (0, 0));
+    }
+
+    #[test]
+    fn open_bus_latch() {
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(0, 0);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuScroll, 0x5A);
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuMask),
+            0x5A
+        );
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuStatus),
+            0x1A
+        );
+
+        // The status read refreshed only the top three bits, which were 0.
+        ppu.write_register(&mut cartridge, PpuRegister::PpuCtrl, 0xFF);
+        ppu.read_register(&mut cartridge, PpuRegister::PpuStatus);
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::OamAddr),
+            0x1F
+        );
+
+        ppu.tick(&mut cartridge, &mut frame, IoLatch::DECAY_DOTS);
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::OamAddr),
+            0x00
+        );
+    }
+
+    #[test]
+    fn open_bus_bits_decay_separately() {
+        let (mut ppu, mut cartridge, mut frame) = ppu_at(0, 0);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuScroll, 0xFF);
+
+        // Halfway through, a status read refreshes only the top three bits (here with the vblank flag clear).
+        ppu.tick(&mut cartridge, &mut frame, IoLatch::DECAY_DOTS / 2);
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuStatus),
+            0x1F
+        );
+        assert_eq!(ppu.peek_register(PpuRegister::PpuMask), 0x1F);
+
+        // The low five bits fade on schedule; the refreshed ones hold on for another half period.
+        ppu.tick(&mut cartridge, &mut frame, IoLatch::DECAY_DOTS / 2);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuStatus, 0xE0);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuMask), 0xE0);
+        ppu.tick(&mut cartridge, &mut frame, IoLatch::DECAY_DOTS - 1);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuMask), 0xE0);
+        ppu.tick(&mut cartridge, &mut frame, 1);
+        assert_eq!(ppu.peek_register(PpuRegister::PpuMask), 0x00);
+    }
+
+    #[test]
+    fn ppu_data_reads_are_delayed_by_the_buffer() {
+        let (mut ppu, mut cartridge, _) = ppu_at(0, 0);
+        cartridge.ppu_memory[0x2000] = 0x11;
+        cartridge.ppu_memory[0x2001] = 0x22;
+        ppu.ppu_data_read_buffer = 0x99;
+        ppu.write_register(&mut cartridge, PpuRegister::PpuCtrl, 0x00);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x20);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x00);
+
+        // Each read returns what the previous one fetched.
+        let mut read = || ppu.read_register(&mut cartridge, PpuRegister::PpuData);
+        assert_eq!([read(), read(), read()], [0x99, 0x11, 0x22]);
+
+        // Writes don't touch the buffer.
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x20);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x00);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuData, 0x33);
+        assert_eq!(ppu.ppu_data_read_buffer, cartridge.ppu_memory[0x2002]);
+        assert_eq!(cartridge.ppu_memory[0x2000], 0x33);
+    }
+
+    #[test]
+    fn palette_reads_fill_the_read_buffer() {
+        let (mut ppu, mut cartridge, _) = ppu_at(0, 0);
+        cartridge.ppu_memory[0x2F00] = 0x42;
+        ppu.palette_ram[0x00] = 0x2C;
+        ppu.write_register(&mut cartridge, PpuRegister::PpuCtrl, 0x00);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x3F);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x00);
+
+        // The top two bits of the palette read come from the $00 just written to $2006.
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::PpuData),
+            0x2C
+        );
+        assert_eq!(ppu.ppu_data_read_buffer, 0x42);
+    }
+
+    #[test]
+    fn ppu_data_access_during_rendering() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            10 * PPU::SCANLINE_LENGTH as u64 + 100,
+        );
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x20);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x00);
+        ppu.read_register(&mut cartridge, PpuRegister::PpuData);
+
+        // Coarse X and fine Y both step, instead of v + 1.
+        assert_eq!(ppu.scroll.current().bits(), 0x3001);
+
+        // Outside rendering, the increment from PPUCTRL applies.
+        let (mut ppu, mut cartridge, _) = ppu_at(250, 0);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuCtrl, 0x04);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x20);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuAddr, 0x00);
+        ppu.write_register(&mut cartridge, PpuRegister::PpuData, 0x00);
+        assert_eq!(ppu.scroll.current().bits(), 0x2020);
+    }
+
+    #[test]
+    fn oam_data_access_during_rendering() {
+        let (mut ppu, mut cartridge, mut frame) = solid_tile_setup();
+        place_sprite(&mut ppu, 0, 16, 9);
+        ppu.tick(
+            &mut cartridge,
+            &mut frame,
+            10 * PPU::SCANLINE_LENGTH as u64 + 30,
+        );
+
+        // Secondary OAM is being cleared.
+        assert_eq!(
+            ppu.read_register(&mut cartridge, PpuRegister::OamData),
+            0xFF
+        );
+
+        // The sprite fetches read back secondary OAM, starting with sprite 0's Y.
+        ppu.tick(&mut cartridge, &mut frame, 257 - 30);
+        assert_eq!(ppu.read_register(&mut cartridge, PpuRegister::OamData), 9);
+
+        // Writes are dropped and move OAMADDR to the next sprite.
+        ppu.write_register(&mut cartridge, PpuRegister::OamData, 0x55);
+        assert_eq!(ppu.oam_addr.bits(), 4);
+        assert!(ppu.oam.as_slice().iter().all(|&byte| byte != 0x55));
+    }
+
+    #[test]
+    fn pal_and_dendy_timing() {
+        let (mut cartridge, mut frame) = (TestCartridge::new(), Frame::new());
+        for (region, vblank_line) in [(Region::Pal, 241), (Region::Dendy, 291)] {
+            let mut ppu = PPU::new();
+            ppu.set_region(region);
+            ppu.write_ppu_mask(0x18);
+
+            let line = PPU::SCANLINE_LENGTH as u64;
+            ppu.tick(&mut cartridge, &mut frame, vblank_line * line + 1);
+            assert_eq!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+            ppu.tick(&mut cartridge, &mut frame, 1);
+            assert_ne!(ppu.peek_register(PpuRegister::PpuStatus) & 0x80, 0);
+
+            // 312 lines, with no skipped dot on odd frames.
+            ppu.tick(&mut cartridge, &mut frame, (312 - vblank_line) * line - 2);
+            assert_eq!((ppu.y, ppu.x), (0, 0));
+            ppu.tick(&mut cartridge, &mut frame, 312 * line);
+            assert_eq!((ppu.y, ppu.x), (0, 0));
+        }
+    }
+}
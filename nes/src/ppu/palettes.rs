@@ -1,66 +1,399 @@
-pub const NTSC_PALETTE: [(u8, u8, u8); 64] = [
-    (82, 82, 82),
-    (1, 26, 81),
-    (15, 15, 101),
-    (35, 6, 99),
-    (54, 3, 75),
-    (64, 4, 38),
-    (63, 9, 4),
-    (50, 19, 0),
-    (31, 32, 0),
-    (11, 42, 0),
-    (0, 47, 0),
-    (0, 46, 10),
-    (0, 38, 45),
-    (0, 0, 0),
-    (0, 0, 0),
-    (0, 0, 0),
-    (160, 160, 160),
-    (30, 74, 157),
-    (56, 55, 188),
-    (88, 40, 184),
-    (117, 33, 148),
-    (132, 35, 92),
-    (130, 46, 36),
-    (111, 63, 0),
-    (81, 82, 0),
-    (49, 99, 0),
-    (26, 107, 5),
-    (14, 105, 46),
-    (16, 92, 104),
-    (0, 0, 0),
-    (0, 0, 0),
-    (0, 0, 0),
-    (254, 255, 255),
-    (105, 158, 252),
-    (137, 135, 255),
-    (174, 118, 255),
-    (206, 109, 241),
-    (224, 112, 178),
-    (222, 124, 112),
-    (200, 145, 62),
-    (166, 167, 37),
-    (129, 186, 40),
-    (99, 196, 70),
-    (84, 193, 125),
-    (86, 179, 192),
-    (60, 60, 60),
-    (0, 0, 0),
-    (0, 0, 0),
-    (254, 255, 255),
-    (190, 214, 253),
-    (204, 204, 255),
-    (221, 196, 255),
-    (234, 192, 249),
-    (242, 193, 223),
-    (241, 199, 194),
-    (232, 208, 170),
-    (217, 218, 157),
-    (201, 226, 158),
-    (188, 230, 174),
-    (180, 229, 199),
-    (181, 223, 228),
-    (169, 169, 169),
-    (0, 0, 0),
-    (0, 0, 0),
+use crate::frame::PixelFormat;
+use std::sync::OnceLock;
+
+/// A lookup table from 9-bit PPU colors (3 emphasis bits above a 6-bit palette index) to RGB.
+#[derive(Clone)]
+pub struct Palette {
+    colors: [(u8, u8, u8); Palette::SIZE],
+    /// Every color encoded in each pixel format, built the first time a frame is converted to that format.
+    encoded: [OnceLock<Box<[[u8; 4]; Palette::SIZE]>>; PixelFormat::COUNT],
+}
+
+impl Palette {
+    pub const SIZE: usize = 512;
+
+    fn from_colors(colors: [(u8, u8, u8); Palette::SIZE]) -> Self {
+        Self {
+            colors,
+            encoded: Default::default(),
+        }
+    }
+
+    /// The default: colors decoded from the 2C02's composite signal with the default tuning.
+    pub fn ntsc() -> Self {
+        Palette::generate_ntsc(&NtscPaletteParameters::default())
+    }
+
+    /// Builds all 512 colors by decoding the composite signal the 2C02 outputs for each one the way a TV would.
+    pub fn generate_ntsc(parameters: &NtscPaletteParameters) -> Self {
+        let mut colors = [(0, 0, 0); Palette::SIZE];
+        for (index, color) in colors.iter_mut().enumerate() {
+            let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+            for phase in 0..12 {
+                let level = composite_level(index as u16, phase);
+                let (cos, sin) = demodulation_carrier(phase, parameters);
+                y += level;
+                i += level * cos;
+                q += level * sin;
+            }
+            *color = yiq_to_rgb(y / 12.0, i / 12.0, q / 12.0, parameters);
+        }
+
+        Palette::from_colors(colors)
+    }
+
+    /// The RGB PPU used in PlayChoice-10 and some Vs. System boards.
+    pub fn rgb_2c03() -> Self {
+        Palette::with_rgb_emphasis(&RGB_PPU_PALETTE)
+    }
+
+    /// The RGB PPUs used in most Vs. System boards, which each scramble the 2C03's colors in their own order to
+    /// stop games being moved to a board they weren't made for.
+    pub fn rgb_2c04(revision: Rgb2c04Revision) -> Self {
+        let permutation = &RGB_2C04_PERMUTATIONS[revision as usize];
+        let base = permutation.map(|index| RGB_PPU_PALETTE[index as usize]);
+        Palette::with_rgb_emphasis(&base)
+    }
+
+    /// The RGB PPU used in the remaining Vs. System boards.
+    pub fn rgb_2c05() -> Self {
+        // The 2C05 only differs from the 2C03 in its register layout, so it shares its colors.
+        Palette::rgb_2c03()
+    }
+
+    /// Loads a .pal file: either 64 colors, which get generated emphasis variants, or all 512 colors, as RGB triplets.
+    pub fn from_pal_file(bytes: &[u8]) -> Result<Self, PaletteLoadError> {
+        let mut triplets = bytes
+            .chunks_exact(3)
+            .map(|triplet| (triplet[0], triplet[1], triplet[2]));
+
+        match bytes.len() {
+            192 => {
+                let mut base = [(0, 0, 0); 64];
+                base.fill_with(|| triplets.next().unwrap());
+                Ok(Palette::with_generated_emphasis(&base))
+            }
+            1536 => {
+                let mut colors = [(0, 0, 0); Palette::SIZE];
+                colors.fill_with(|| triplets.next().unwrap());
+                Ok(Palette::from_colors(colors))
+            }
+            length => Err(PaletteLoadError::UnsupportedLength(length)),
+        }
+    }
+
+    /// Builds a full palette from 64 base colors, approximating each emphasis combination by attenuating the
+    /// channels that aren't emphasized.
+    pub fn with_generated_emphasis(base: &[(u8, u8, u8); 64]) -> Self {
+        const ATTENUATION: f32 = 0.816328;
+
+        let mut colors = [(0, 0, 0); Palette::SIZE];
+        for (emphasis, variant) in colors.chunks_exact_mut(64).enumerate() {
+            let attenuate = |value: u8, emphasized: bool| match emphasis != 0 && !emphasized {
+                true => (value as f32 * ATTENUATION).round() as u8,
+                false => value,
+            };
+
+            for (color, (r, g, b)) in variant.iter_mut().zip(base.iter()) {
+                *color = (
+                    attenuate(*r, emphasis & 0x01 != 0),
+                    attenuate(*g, emphasis & 0x02 != 0),
+                    attenuate(*b, emphasis & 0x04 != 0),
+                );
+            }
+        }
+
+        Palette::from_colors(colors)
+    }
+
+    /// Builds a full palette for an RGB PPU from its 3-bit-per-channel colors. On these, an emphasis bit drives its
+    /// channel to full brightness instead of darkening the others.
+    fn with_rgb_emphasis(base: &[u16; 64]) -> Self {
+        let scale = |level: u16| (level * 255 / 7) as u8;
+
+        let mut colors = [(0, 0, 0); Palette::SIZE];
+        for (emphasis, variant) in colors.chunks_exact_mut(64).enumerate() {
+            let channel = |level: u16, bit: usize| match emphasis & bit != 0 {
+                true => 255,
+                false => scale(level),
+            };
+
+            for (color, rgb) in variant.iter_mut().zip(base.iter()) {
+                *color = (
+                    channel(rgb >> 6 & 0x07, 0x01),
+                    channel(rgb >> 3 & 0x07, 0x02),
+                    channel(rgb & 0x07, 0x04),
+                );
+            }
+        }
+
+        Palette::from_colors(colors)
+    }
+
+    /// Looks up a 9-bit color: bits 0-5 are the palette RAM value, bits 6-8 the red, green and blue emphasis bits.
+    pub fn color(&self, index: u16) -> (u8, u8, u8) {
+        self.colors[index as usize % Palette::SIZE]
+    }
+
+    /// Every color encoded in `format`, with the unused bytes zeroed.
+    pub(crate) fn encoded(&self, format: PixelFormat) -> &[[u8; 4]; Palette::SIZE] {
+        self.encoded[format as usize].get_or_init(|| {
+            let mut encoded = Box::new([[0; 4]; Palette::SIZE]);
+            for (pixel, color) in encoded.iter_mut().zip(&self.colors) {
+                *pixel = format.encode(*color);
+            }
+            encoded
+        })
+    }
+}
+
+/// The 2C02's composite output for a 9-bit color at one of the 12 phases of the color subcarrier, scaled so black is 0
+/// and white is 1.
+///
+/// Each color is a square wave: high for the 6 phases matching its hue and low for the other 6, at voltages picked by
+/// its luma. Hue 0 stays high, hues $D-$F stay low, and an emphasis bit dims the phases belonging to its color.
+pub(crate) fn composite_level(color: u16, phase: usize) -> f64 {
+    const LOW_LEVELS: [f64; 4] = [0.228, 0.312, 0.552, 0.880];
+    const HIGH_LEVELS: [f64; 4] = [0.616, 0.840, 1.100, 1.100];
+    const BLACK: f64 = 0.312;
+    const WHITE: f64 = 1.100;
+    const EMPHASIS_ATTENUATION: f64 = 0.746;
+
+    let in_color_phase = |hue: usize| (hue + phase) % 12 < 6;
+
+    let hue = (color & 0x0F) as usize;
+    let emphasis = color >> 6;
+    // $xE and $xF output the same black as $1D.
+    let luma = match hue {
+        0x0E | 0x0F => 1,
+        _ => (color as usize >> 4) & 0x03,
+    };
+
+    let high = match hue {
+        0x00 => true,
+        0x0D..=0x0F => false,
+        _ => in_color_phase(hue),
+    };
+    let mut signal = match high {
+        true => HIGH_LEVELS[luma],
+        false => LOW_LEVELS[luma],
+    };
+
+    let attenuated = (emphasis & 0x01 != 0 && in_color_phase(0x0C))
+        || (emphasis & 0x02 != 0 && in_color_phase(0x04))
+        || (emphasis & 0x04 != 0 && in_color_phase(0x08));
+    if attenuated && hue < 0x0E {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+/// The cosine and sine a TV multiplies the signal by at a subcarrier phase to recover I and Q.
+pub(crate) fn demodulation_carrier(phase: usize, parameters: &NtscPaletteParameters) -> (f64, f64) {
+    // Puts hue 8 on the color burst, which is where the PPU's phases land relative to a TV's decoder.
+    const PHASE_OFFSET: f64 = 4.0;
+
+    let angle =
+        std::f64::consts::PI / 6.0 * (phase as f64 + PHASE_OFFSET) + parameters.hue.to_radians();
+    (angle.cos(), angle.sin())
+}
+
+/// Applies the picture controls to a decoded YIQ color and converts it to RGB.
+pub(crate) fn yiq_to_rgb(
+    y: f64,
+    i: f64,
+    q: f64,
+    parameters: &NtscPaletteParameters,
+) -> (u8, u8, u8) {
+    let gamma_correct = |value: f64| value.max(0.0).powf(2.2 / parameters.gamma);
+    let to_byte = |value: f64| (gamma_correct(value) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    let y = y * parameters.contrast + parameters.brightness;
+    let i = i * parameters.saturation * parameters.contrast;
+    let q = q * parameters.saturation * parameters.contrast;
+
+    // FCC YIQ to RGB.
+    (
+        to_byte(y + 0.946882 * i + 0.623557 * q),
+        to_byte(y - 0.274788 * i - 0.635691 * q),
+        to_byte(y - 1.108545 * i + 1.709007 * q),
+    )
+}
+
+/// Tuning for the NTSC palette generator, in the spirit of a TV's picture controls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtscPaletteParameters {
+    /// Rotates every hue, in degrees.
+    pub hue: f64,
+    /// Scales the chroma, so 0 gives grayscale.
+    pub saturation: f64,
+    /// Scales luma and chroma together.
+    pub contrast: f64,
+    /// Added to luma, where 1 is the distance from black to white.
+    pub brightness: f64,
+    /// The display gamma to correct for. The signal is already encoded for 2.2, so 2.2 leaves it alone.
+    pub gamma: f64,
+}
+
+impl Default for NtscPaletteParameters {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 2.2,
+        }
+    }
+}
+
+/// The four 2C04 part numbers, RP2C04-0001 to RP2C04-0004, each with its own color order.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rgb2c04Revision {
+    Rp2c04_0001,
+    Rp2c04_0002,
+    Rp2c04_0003,
+    Rp2c04_0004,
+}
+
+impl Rgb2c04Revision {
+    pub const ALL: [Rgb2c04Revision; 4] = [
+        Rgb2c04Revision::Rp2c04_0001,
+        Rgb2c04Revision::Rp2c04_0002,
+        Rgb2c04Revision::Rp2c04_0003,
+        Rgb2c04Revision::Rp2c04_0004,
+    ];
+}
+
+#[derive(Debug)]
+pub enum PaletteLoadError {
+    UnsupportedLength(usize),
+}
+
+impl std::fmt::Display for PaletteLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaletteLoadError::UnsupportedLength(length) => write!(
+                f,
+                "a .pal file should hold 64 or 512 RGB colors (192 or 1536 bytes), not {length} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PaletteLoadError {}
+
+/// The 2C03's colors as octal RGB levels from 0 to 7.
+#[rustfmt::skip]
+const RGB_PPU_PALETTE: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o000, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
 ];
+
+/// Where each of the 2C04 revisions' colors lives in the 2C03's palette, in [`Rgb2c04Revision`] order. The 2C03 has a
+/// few duplicate colors, so some of its entries don't appear, and the 2C04s repeat its black and white instead.
+#[rustfmt::skip]
+const RGB_2C04_PERMUTATIONS: [[u8; 64]; 4] = [
+    [
+        0x35, 0x23, 0x16, 0x22, 0x1C, 0x09, 0x1D, 0x15, 0x20, 0x00, 0x27, 0x05, 0x04, 0x28, 0x08, 0x20,
+        0x21, 0x3E, 0x1F, 0x29, 0x3C, 0x32, 0x36, 0x12, 0x3F, 0x2B, 0x2E, 0x1E, 0x3D, 0x2D, 0x24, 0x01,
+        0x0E, 0x31, 0x33, 0x2A, 0x2C, 0x0C, 0x1B, 0x14, 0x2E, 0x07, 0x34, 0x06, 0x13, 0x02, 0x26, 0x2E,
+        0x2E, 0x19, 0x10, 0x0A, 0x39, 0x03, 0x37, 0x17, 0x0F, 0x11, 0x0B, 0x0D, 0x38, 0x25, 0x18, 0x3A,
+    ],
+    [
+        0x2E, 0x27, 0x18, 0x39, 0x3A, 0x25, 0x1C, 0x31, 0x16, 0x13, 0x38, 0x34, 0x20, 0x23, 0x3C, 0x0B,
+        0x0F, 0x21, 0x06, 0x3D, 0x1B, 0x29, 0x1E, 0x22, 0x1D, 0x24, 0x0E, 0x2B, 0x32, 0x08, 0x2E, 0x03,
+        0x04, 0x36, 0x26, 0x33, 0x11, 0x1F, 0x10, 0x02, 0x14, 0x3F, 0x00, 0x09, 0x12, 0x2E, 0x28, 0x20,
+        0x3E, 0x0D, 0x2A, 0x17, 0x0C, 0x01, 0x15, 0x19, 0x2E, 0x2C, 0x07, 0x37, 0x35, 0x05, 0x0A, 0x2D,
+    ],
+    [
+        0x14, 0x25, 0x3A, 0x10, 0x0B, 0x20, 0x31, 0x09, 0x01, 0x2E, 0x36, 0x08, 0x15, 0x3D, 0x3E, 0x3C,
+        0x22, 0x1C, 0x05, 0x12, 0x19, 0x18, 0x17, 0x1B, 0x00, 0x03, 0x2E, 0x02, 0x16, 0x06, 0x34, 0x35,
+        0x23, 0x0F, 0x0E, 0x37, 0x0D, 0x27, 0x26, 0x20, 0x29, 0x04, 0x21, 0x24, 0x11, 0x2D, 0x2E, 0x1F,
+        0x2C, 0x1E, 0x39, 0x33, 0x07, 0x2A, 0x28, 0x1D, 0x0A, 0x2E, 0x32, 0x38, 0x13, 0x2B, 0x3F, 0x0C,
+    ],
+    [
+        0x18, 0x03, 0x1C, 0x28, 0x2E, 0x35, 0x01, 0x17, 0x10, 0x1F, 0x2A, 0x0E, 0x36, 0x37, 0x0B, 0x39,
+        0x25, 0x1E, 0x12, 0x34, 0x2E, 0x1D, 0x06, 0x26, 0x3E, 0x1B, 0x22, 0x19, 0x04, 0x2E, 0x3A, 0x21,
+        0x05, 0x0A, 0x07, 0x02, 0x13, 0x14, 0x00, 0x15, 0x0C, 0x3D, 0x11, 0x0F, 0x0D, 0x38, 0x2D, 0x24,
+        0x33, 0x20, 0x08, 0x16, 0x3F, 0x2B, 0x20, 0x3C, 0x2E, 0x27, 0x23, 0x31, 0x29, 0x32, 0x2C, 0x09,
+    ],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pal_files() {
+        let base: Vec<u8> = (0..192).map(|i| i as u8).collect();
+        let palette = Palette::from_pal_file(&base).unwrap();
+        assert_eq!(palette.color(0x01), (3, 4, 5));
+        assert_eq!(palette.color(0x41).0, 3);
+        assert_ne!(palette.color(0x41).1, 4);
+
+        let full: Vec<u8> = (0..1536).map(|i| (i / 3) as u8).collect();
+        let palette = Palette::from_pal_file(&full).unwrap();
+        assert_eq!(palette.color(0x101), (1, 1, 1));
+
+        let error = Palette::from_pal_file(&full[..100]).err().unwrap();
+        assert!(error.to_string().contains("not 100 bytes"));
+    }
+
+    #[test]
+    fn generated_ntsc_palette() {
+        let palette = Palette::ntsc();
+        assert_eq!(palette.color(0x0F), (0, 0, 0));
+        assert_eq!(palette.color(0x30), (255, 255, 255));
+
+        let dominant = |(r, g, b): (u8, u8, u8)| match r.max(g).max(b) {
+            max if max == r => 'r',
+            max if max == g => 'g',
+            _ => 'b',
+        };
+        assert_eq!(dominant(palette.color(0x16)), 'r');
+        assert_eq!(dominant(palette.color(0x1A)), 'g');
+        assert_eq!(dominant(palette.color(0x12)), 'b');
+
+        // Emphasizing red darkens gray's green and blue, but leaves black alone.
+        let (r, g, b) = palette.color(0x40 | 0x10);
+        assert!(r > g && r > b);
+        assert_eq!(palette.color(0x40 | 0x0F), (0, 0, 0));
+
+        let grayscale = Palette::generate_ntsc(&NtscPaletteParameters {
+            saturation: 0.0,
+            ..Default::default()
+        });
+        let (r, g, b) = grayscale.color(0x16);
+        assert!(r == g && g == b);
+    }
+
+    #[test]
+    fn rgb_ppu_emphasis() {
+        let palette = Palette::rgb_2c03();
+        assert_eq!(palette.color(0x30), (255, 255, 255));
+        assert_eq!(palette.color(0x0F), (0, 0, 0));
+        assert_eq!(palette.color(0x40 | 0x0F), (255, 0, 0));
+
+        // The 2C04s have the same set of colors, just in a different order.
+        let colors = |palette: &Palette| {
+            let mut colors: Vec<_> = (0..64).map(|index| palette.color(index)).collect();
+            colors.sort();
+            colors.dedup();
+            colors
+        };
+        for revision in Rgb2c04Revision::ALL {
+            let scrambled = Palette::rgb_2c04(revision);
+            assert_eq!(colors(&scrambled), colors(&palette));
+            assert_ne!(scrambled.color(0x00), palette.color(0x00));
+        }
+        assert_eq!(
+            Palette::rgb_2c04(Rgb2c04Revision::Rp2c04_0001).color(0x00),
+            palette.color(0x35)
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+/// The PPU's I/O bus. It holds the last value driven onto it, which reads of write-only registers (and unused status
+/// bits) return. Each bit fades to 0 if nothing refreshes it for a while.
+#[derive(Clone)]
+pub struct IoLatch {
+    value: u8,
+    refreshed_at: [u64; 8],
+}
+
+impl IoLatch {
+    /// Roughly 600 ms worth of PPU dots. Real hardware varies with temperature; the test ROMs accept anything from
+    /// about 100 ms to a second.
+    pub const DECAY_DOTS: u64 = 3_200_000;
+
+    pub fn new() -> Self {
+        Self {
+            value: 0,
+            refreshed_at: [0; 8],
+        }
+    }
+
+    /// The latch's value at the given dot, without updating the decayed bits.
+    pub fn peek(&self, now: u64) -> u8 {
+        let mut value = self.value;
+        for (bit, refreshed_at) in self.refreshed_at.iter().enumerate() {
+            if now.saturating_sub(*refreshed_at) >= IoLatch::DECAY_DOTS {
+                value &= !(1 << bit);
+            }
+        }
+        value
+    }
+
+    pub fn read(&mut self, now: u64) -> u8 {
+        self.value = self.peek(now);
+        self.value
+    }
+
+    /// Drives the bits selected by `mask` onto the bus, leaving the others as they were.
+    pub fn refresh(&mut self, value: u8, mask: u8, now: u64) {
+        self.value = (self.peek(now) & !mask) | (value & mask);
+        for (bit, refreshed_at) in self.refreshed_at.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *refreshed_at = now;
+            }
+        }
+    }
+}
+
+impl Snapshot for IoLatch {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.value);
+        for refreshed_at in self.refreshed_at {
+            writer.write_u64(refreshed_at);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.value = reader.read_u8()?;
+        for refreshed_at in self.refreshed_at.iter_mut() {
+            *refreshed_at = reader.read_u64()?;
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,13 @@
+mod io_latch;
 mod oam_addr;
-mod ppu_addr;
 mod ppu_ctrl;
 mod ppu_mask;
-mod ppu_scroll;
 mod ppu_status;
+mod vram_address;
 
+pub use io_latch::IoLatch;
 pub use oam_addr::OamAddr;
-pub use ppu_addr::PpuAddr;
 pub use ppu_ctrl::PpuCtrl;
 pub use ppu_mask::PpuMask;
-pub use ppu_scroll::PpuScroll;
 pub use ppu_status::PpuStatus;
+pub use vram_address::ScrollRegisters;
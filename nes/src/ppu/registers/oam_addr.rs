@@ -1,3 +1,6 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+#[derive(Clone)]
 pub struct OamAddr(u8);
 
 impl OamAddr {
@@ -17,7 +20,22 @@ impl OamAddr {
         self.0 = self.0.wrapping_add(1);
     }
 
+    pub fn increment_sprite(&mut self) {
+        self.0 = self.0.wrapping_add(4);
+    }
+
     pub fn reset_latch(&mut self) {
         self.0 = 0;
     }
 }
+
+impl Snapshot for OamAddr {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0 = reader.read_u8()?;
+        Ok(())
+    }
+}
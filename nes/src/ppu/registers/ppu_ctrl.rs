@@ -1,5 +1,7 @@
 use super::super::rendering::SpriteSize;
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
 
+#[derive(Clone)]
 pub struct PpuCtrl(u8);
 
 impl PpuCtrl {
@@ -15,16 +17,6 @@ impl PpuCtrl {
         self.0 = byte;
     }
 
-    pub fn nametable_base_address(&self) -> u16 {
-        match self.0 & 0x03 {
-            0 => 0x2000,
-            1 => 0x2400,
-            2 => 0x2800,
-            3 => 0x2C00,
-            _ => unreachable!(),
-        }
-    }
-
     pub fn vram_address_increment(&self) -> u16 {
         match self.0 & 0x04 != 0 {
             false => 1,
@@ -57,3 +49,14 @@ impl PpuCtrl {
         self.0 & 0x80 != 0
     }
 }
+
+impl Snapshot for PpuCtrl {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0 = reader.read_u8()?;
+        Ok(())
+    }
+}
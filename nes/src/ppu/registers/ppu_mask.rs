@@ -1,3 +1,6 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+#[derive(Clone)]
 pub struct PpuMask(u8);
 
 impl PpuMask {
@@ -44,4 +47,22 @@ impl PpuMask {
     pub fn emphasize_blue(&self) -> bool {
         self.0 & 0x80 != 0
     }
+
+    /// The emphasis bits in palette lookup order: red in bit 0, green in bit 1, blue in bit 2.
+    pub fn emphasis(&self) -> u16 {
+        (self.emphasize_red() as u16)
+            | (self.emphasize_green() as u16) << 1
+            | (self.emphasize_blue() as u16) << 2
+    }
+}
+
+impl Snapshot for PpuMask {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0 = reader.read_u8()?;
+        Ok(())
+    }
 }
@@ -1,3 +1,6 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+#[derive(Clone)]
 pub struct PpuStatus(u8);
 
 impl PpuStatus {
@@ -15,6 +18,10 @@ impl PpuStatus {
         value
     }
 
+    pub fn vblank_started(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
     pub fn set_sprite_overflow(&mut self, status: bool) {
         self.0 &= 0xDF;
         self.0 |= (status as u8) << 5;
@@ -30,3 +37,14 @@ impl PpuStatus {
         self.0 |= (status as u8) << 7;
     }
 }
+
+impl Snapshot for PpuStatus {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0 = reader.read_u8()?;
+        Ok(())
+    }
+}
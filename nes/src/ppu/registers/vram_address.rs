@@ -0,0 +1,232 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
+/// A 15-bit VRAM address in the layout the PPU uses for scrolling: `yyy NN YYYYY XXXXX` (fine Y, nametable select,
+/// coarse Y, coarse X).
+#[derive(Clone, Copy)]
+pub struct VramAddress(u16);
+
+impl VramAddress {
+    const COARSE_X: u16 = 0x001F;
+    const COARSE_Y: u16 = 0x03E0;
+    const NAMETABLE_X: u16 = 0x0400;
+    const NAMETABLE_Y: u16 = 0x0800;
+    const FINE_Y: u16 = 0x7000;
+    const HORIZONTAL: u16 = Self::COARSE_X | Self::NAMETABLE_X;
+    const VERTICAL: u16 = Self::COARSE_Y | Self::NAMETABLE_Y | Self::FINE_Y;
+
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn coarse_x(&self) -> u16 {
+        self.0 & Self::COARSE_X
+    }
+
+    pub fn coarse_y(&self) -> u16 {
+        (self.0 & Self::COARSE_Y) >> 5
+    }
+
+    pub fn fine_y(&self) -> u16 {
+        (self.0 & Self::FINE_Y) >> 12
+    }
+
+    pub fn tile_address(&self) -> u16 {
+        0x2000 | (self.0 & 0x0FFF)
+    }
+
+    pub fn attribute_address(&self) -> u16 {
+        0x23C0 | (self.0 & 0x0C00) | ((self.0 >> 4) & 0x38) | ((self.0 >> 2) & 0x07)
+    }
+
+    pub fn attribute_shift(&self) -> u16 {
+        (self.coarse_y() & 0x02) << 1 | (self.coarse_x() & 0x02)
+    }
+
+    pub fn increment(&mut self, increment: u16) {
+        self.0 = self.0.wrapping_add(increment) & 0x7FFF;
+    }
+
+    pub fn increment_coarse_x(&mut self) {
+        if self.coarse_x() == 31 {
+            self.0 &= !Self::COARSE_X;
+            self.0 ^= Self::NAMETABLE_X;
+        } else {
+            self.0 += 1;
+        }
+    }
+
+    pub fn increment_y(&mut self) {
+        if self.fine_y() < 7 {
+            self.0 += 0x1000;
+            return;
+        }
+
+        self.0 &= !Self::FINE_Y;
+        let coarse_y = match self.coarse_y() {
+            29 => {
+                self.0 ^= Self::NAMETABLE_Y;
+                0
+            }
+            // Coarse Y can be set out of bounds, in which case it wraps without switching nametables.
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.0 = (self.0 & !Self::COARSE_Y) | (coarse_y << 5);
+    }
+
+    fn copy(&mut self, other: &VramAddress, mask: u16) {
+        self.0 = (self.0 & !mask) | (other.0 & mask);
+    }
+}
+
+/// The PPU's internal scroll and address registers ("loopy" registers), shared by PPUCTRL, PPUSCROLL and PPUADDR.
+#[derive(Clone)]
+pub struct ScrollRegisters {
+    /// The current VRAM address.
+    v: VramAddress,
+    /// The temporary VRAM address; the top-left onscreen tile.
+    t: VramAddress,
+    fine_x: u8,
+    /// The first/second write toggle shared by PPUSCROLL and PPUADDR.
+    w: bool,
+}
+
+impl ScrollRegisters {
+    pub fn new() -> Self {
+        Self {
+            v: VramAddress::new(),
+            t: VramAddress::new(),
+            fine_x: 0,
+            w: false,
+        }
+    }
+
+    pub fn current(&self) -> VramAddress {
+        self.v
+    }
+
+    pub fn current_mut(&mut self) -> &mut VramAddress {
+        &mut self.v
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.fine_x
+    }
+
+    /// The address used by PPUDATA accesses.
+    pub fn address(&self) -> u16 {
+        self.v.bits() & 0x3FFF
+    }
+
+    // $2000 write
+    pub fn write_ppu_ctrl(&mut self, value: u8) {
+        let nametable = (value as u16 & 0x03) << 10;
+        self.t.copy(
+            &VramAddress(nametable),
+            VramAddress::NAMETABLE_X | VramAddress::NAMETABLE_Y,
+        );
+    }
+
+    // $2005 write
+    pub fn write_ppu_scroll(&mut self, value: u8) {
+        if !self.w {
+            self.t
+                .copy(&VramAddress(value as u16 >> 3), VramAddress::COARSE_X);
+            self.fine_x = value & 0x07;
+        } else {
+            let bits = (value as u16 & 0x07) << 12 | (value as u16 >> 3) << 5;
+            self.t.copy(
+                &VramAddress(bits),
+                VramAddress::COARSE_Y | VramAddress::FINE_Y,
+            );
+        }
+        self.w = !self.w;
+    }
+
+    // $2006 write
+    pub fn write_ppu_addr(&mut self, value: u8) {
+        if !self.w {
+            // The top bit of fine Y is cleared by the first write.
+            self.t
+                .copy(&VramAddress((value as u16 & 0x3F) << 8), 0x7F00);
+        } else {
+            self.t.copy(&VramAddress(value as u16), 0x00FF);
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    // $2002 read
+    pub fn reset_latch(&mut self) {
+        self.w = false;
+    }
+
+    pub fn copy_horizontal(&mut self) {
+        self.v.copy(&self.t, VramAddress::HORIZONTAL);
+    }
+
+    pub fn copy_vertical(&mut self) {
+        self.v.copy(&self.t, VramAddress::VERTICAL);
+    }
+}
+
+impl Snapshot for ScrollRegisters {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.v.0);
+        writer.write_u16(self.t.0);
+        writer.write_u8(self.fine_x);
+        writer.write_bool(self.w);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.v = VramAddress(reader.read_u16()? & 0x7FFF);
+        self.t = VramAddress(reader.read_u16()? & 0x7FFF);
+        self.fine_x = reader.read_u8()? & 0x07;
+        self.w = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_x_y_scroll() {
+        // The mid-frame $2006/$2005/$2005/$2006 sequence from the nesdev wiki.
+        let mut registers = ScrollRegisters::new();
+        registers.write_ppu_addr(0x04);
+        registers.write_ppu_scroll(0x3E);
+        registers.write_ppu_scroll(0x7D);
+        registers.write_ppu_addr(0xEF);
+
+        assert_eq!(registers.current().bits(), 0x64EF);
+        assert_eq!(registers.current().coarse_x(), 15);
+        assert_eq!(registers.current().coarse_y(), 7);
+        assert_eq!(registers.current().fine_y(), 6);
+        assert_eq!(registers.fine_x(), 5);
+    }
+
+    #[test]
+    fn increments_wrap_into_next_nametable() {
+        let mut registers = ScrollRegisters::new();
+        registers.write_ppu_scroll(0xF8);
+        registers.write_ppu_scroll(0xEF);
+        registers.copy_horizontal();
+        registers.copy_vertical();
+
+        let v = registers.current_mut();
+        v.increment_coarse_x();
+        assert_eq!(v.coarse_x(), 0);
+        assert_eq!(v.tile_address() & 0x0C00, 0x0400);
+
+        v.increment_y();
+        assert_eq!(v.coarse_y(), 0);
+        assert_eq!(v.fine_y(), 0);
+        assert_eq!(v.tile_address() & 0x0C00, 0x0C00);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::save_state::{SaveStateError, Snapshot, StateReader, StateWriter};
+
 #[derive(Clone, Copy)]
 pub enum SpriteSize {
     EightByEight,
@@ -37,11 +39,11 @@ impl<'a> Sprite<'a> {
     }
 
     pub fn palette_section(&self) -> u8 {
-        self.bytes[2] & 0x03 + 4
+        (self.bytes[2] & 0x03) + 4
     }
 
     pub fn above_background(&self) -> bool {
-        self.bytes[2] & 0x20 != 0
+        self.bytes[2] & 0x20 == 0
     }
 
     pub fn flipped_horizontally(&self) -> bool {
@@ -52,23 +54,39 @@ impl<'a> Sprite<'a> {
         self.bytes[2] & 0x80 != 0
     }
 
-    pub fn contains_point(&self, x: usize, y: usize, size: SpriteSize) -> bool {
-        let width = 8;
-        let height = match size {
+    pub fn height(size: SpriteSize) -> u16 {
+        match size {
             SpriteSize::EightByEight => 8,
             SpriteSize::EightBySixteen => 16,
+        }
+    }
+
+    /// Returns the row of the sprite that falls on the given scanline, if any. Sprites are drawn one line below their
+    /// Y coordinate, so the row returned here is the one fetched during the preceding line's sprite evaluation.
+    pub fn row_on_scanline(&self, scanline: u16, size: SpriteSize) -> Option<u16> {
+        let row = scanline.wrapping_sub(self.y_pos() as u16);
+        (row < Self::height(size)).then_some(row)
+    }
+
+    pub fn pattern_address(&self, row: u16, size: SpriteSize, pattern_table_address: u16) -> u16 {
+        let row = match self.flipped_vertically() {
+            false => row,
+            true => Self::height(size) - 1 - row,
         };
-        let x_pos = self.x_pos() as usize;
-        let y_pos = self.y_pos() as usize;
 
-        if x >= x_pos && x < x_pos + width && y >= y_pos && y < y_pos + height {
-            true
-        } else {
-            false
+        match size {
+            SpriteSize::EightByEight => {
+                pattern_table_address | (self.tile_index(size) as u16) << 4 | row
+            }
+            SpriteSize::EightBySixteen => {
+                let tile = self.tile_index(size) as u16 + row / 8;
+                self.bank_for_eight_by_sixteen_sprite() | tile << 4 | (row % 8)
+            }
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct TileSlice {
     lower_bit_plane: u8,
     upper_bit_plane: u8,
@@ -90,23 +108,92 @@ impl TileSlice {
     }
 }
 
-pub struct BackgroundSlice {
+/// The tile data fetched for the next background tile, waiting to be loaded into the shift registers.
+#[derive(Clone, Default)]
+pub struct BackgroundLatches {
+    pub tile_index: u8,
+    pub palette_section: u8,
+    pub lower_bit_plane: u8,
+    pub upper_bit_plane: u8,
+}
+
+/// The background shift registers. The high byte holds the tile being drawn, the low byte the next tile.
+#[derive(Clone, Default)]
+pub struct BackgroundShifter {
+    lower_bit_plane: u16,
+    upper_bit_plane: u16,
+    lower_palette_bit: u16,
+    upper_palette_bit: u16,
+}
+
+impl BackgroundShifter {
+    pub fn reload(&mut self, lower_bit_plane: u8, upper_bit_plane: u8, palette_section: u8) {
+        let expand = |bit: bool| if bit { 0xFF } else { 0x00 };
+        self.lower_bit_plane = (self.lower_bit_plane & 0xFF00) | lower_bit_plane as u16;
+        self.upper_bit_plane = (self.upper_bit_plane & 0xFF00) | upper_bit_plane as u16;
+        self.lower_palette_bit =
+            (self.lower_palette_bit & 0xFF00) | expand(palette_section & 0x01 != 0);
+        self.upper_palette_bit =
+            (self.upper_palette_bit & 0xFF00) | expand(palette_section & 0x02 != 0);
+    }
+
+    pub fn shift(&mut self) {
+        self.lower_bit_plane <<= 1;
+        self.upper_bit_plane <<= 1;
+        self.lower_palette_bit <<= 1;
+        self.upper_palette_bit <<= 1;
+    }
+
+    pub fn color(&self, fine_x: u8) -> u16 {
+        let mask = 0x8000 >> fine_x;
+        let bit = |register: u16| (register & mask != 0) as u16;
+
+        let pattern_color = bit(self.upper_bit_plane) << 1 | bit(self.lower_bit_plane);
+        if pattern_color != 0 {
+            (bit(self.upper_palette_bit) << 1 | bit(self.lower_palette_bit)) << 2 | pattern_color
+        } else {
+            0
+        }
+    }
+}
+
+/// One row of a sprite, fetched during sprite evaluation and drawn on the following scanline.
+#[derive(Clone, Copy)]
+pub struct SpriteSlice {
     tile_slice: TileSlice,
+    x: u8,
     palette_section: u8,
+    above_background: bool,
 }
 
-impl BackgroundSlice {
-    pub fn new(lower_bit_plane: u8, upper_bit_plane: u8, palette_section: u8) -> Self {
-        let tile_slice = TileSlice::new(lower_bit_plane, upper_bit_plane);
+impl SpriteSlice {
+    pub fn new(sprite: &Sprite, lower_bit_plane: u8, upper_bit_plane: u8) -> Self {
+        let tile_slice = match sprite.flipped_horizontally() {
+            false => TileSlice::new(lower_bit_plane, upper_bit_plane),
+            true => TileSlice::new(
+                lower_bit_plane.reverse_bits(),
+                upper_bit_plane.reverse_bits(),
+            ),
+        };
         Self {
             tile_slice,
-            palette_section,
+            x: sprite.x_pos(),
+            palette_section: sprite.palette_section(),
+            above_background: sprite.above_background(),
         }
     }
 
-    pub fn color(&self, pixel: u16) -> u16 {
-        let pattern_color = self.tile_slice.pattern_color(pixel);
+    pub fn above_background(&self) -> bool {
+        self.above_background
+    }
 
+    pub fn color(&self, x: u16) -> u16 {
+        let pixel = x.wrapping_sub(self.x as u16);
+        if pixel >= 8 {
+            return 0;
+        }
+
+        let pattern_color = self.tile_slice.pattern_color(pixel);
         if pattern_color != 0 {
             (self.palette_section as u16) << 2 | pattern_color
         } else {
@@ -114,3 +201,84 @@ impl BackgroundSlice {
         }
     }
 }
+
+impl Snapshot for BackgroundLatches {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.tile_index);
+        writer.write_u8(self.palette_section);
+        writer.write_u8(self.lower_bit_plane);
+        writer.write_u8(self.upper_bit_plane);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.tile_index = reader.read_u8()?;
+        self.palette_section = reader.read_u8()?;
+        self.lower_bit_plane = reader.read_u8()?;
+        self.upper_bit_plane = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+impl Snapshot for BackgroundShifter {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.lower_bit_plane);
+        writer.write_u16(self.upper_bit_plane);
+        writer.write_u16(self.lower_palette_bit);
+        writer.write_u16(self.upper_palette_bit);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.lower_bit_plane = reader.read_u16()?;
+        self.upper_bit_plane = reader.read_u16()?;
+        self.lower_palette_bit = reader.read_u16()?;
+        self.upper_palette_bit = reader.read_u16()?;
+        Ok(())
+    }
+}
+
+impl SpriteSlice {
+    pub fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.tile_slice.lower_bit_plane);
+        writer.write_u8(self.tile_slice.upper_bit_plane);
+        writer.write_u8(self.x);
+        writer.write_u8(self.palette_section);
+        writer.write_bool(self.above_background);
+    }
+
+    pub fn load(reader: &mut StateReader) -> Result<Self, SaveStateError> {
+        Ok(Self {
+            tile_slice: TileSlice::new(reader.read_u8()?, reader.read_u8()?),
+            x: reader.read_u8()?,
+            palette_section: reader.read_u8()?,
+            above_background: reader.read_bool()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprite_attributes() {
+        let sprite = Sprite::new(&[0x10, 0x21, 0xE3, 0x40]);
+        assert_eq!(sprite.palette_section(), 7);
+        assert!(!sprite.above_background());
+        assert!(sprite.flipped_horizontally());
+        assert!(sprite.flipped_vertically());
+    }
+
+    #[test]
+    fn eight_by_sixteen_sprite_rows() {
+        let size = SpriteSize::EightBySixteen;
+        let sprite = Sprite::new(&[0x10, 0x21, 0x00, 0x40]);
+        assert_eq!(sprite.row_on_scanline(0x10, size), Some(0));
+        assert_eq!(sprite.row_on_scanline(0x20, size), None);
+        assert_eq!(sprite.pattern_address(3, size, 0), 0x1203);
+        assert_eq!(sprite.pattern_address(11, size, 0), 0x1213);
+
+        let flipped = Sprite::new(&[0x10, 0x21, 0x80, 0x40]);
+        assert_eq!(flipped.pattern_address(0, size, 0), 0x1217);
+        assert_eq!(flipped.pattern_address(15, size, 0), 0x1200);
+    }
+}
@@ -0,0 +1,79 @@
+/// The console variant being emulated, which decides the PPU's frame layout and how fast it runs relative to the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    /// The common Famiclone timing: PAL's 312 lines and 50 Hz refresh, but NTSC's 3:1 clock ratio and a vblank that
+    /// starts 50 lines late so NTSC games get as much CPU time per line.
+    Dendy,
+}
+
+impl Region {
+    pub fn scanlines(&self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// The line whose second dot sets the vblank flag.
+    pub fn nmi_scanline(&self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    pub fn pre_render_scanline(&self) -> u16 {
+        self.scanlines() - 1
+    }
+
+    /// Only the NTSC PPU drops a dot from odd frames while rendering.
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        *self == Region::Ntsc
+    }
+
+    /// PAL-derived PPUs swap the red and green emphasis bits in PPUMASK.
+    pub fn swaps_red_green_emphasis(&self) -> bool {
+        *self != Region::Ntsc
+    }
+
+    pub fn master_clock_hz(&self) -> f64 {
+        match self {
+            Region::Ntsc => 236_250_000.0 / 11.0,
+            Region::Pal | Region::Dendy => 26_601_712.5,
+        }
+    }
+
+    /// Master clock cycles per CPU cycle and per PPU dot.
+    pub fn clock_dividers(&self) -> (u64, u64) {
+        match self {
+            Region::Ntsc => (12, 4),
+            Region::Pal => (16, 5),
+            Region::Dendy => (15, 5),
+        }
+    }
+
+    /// Frames per second, for pacing a front end. NTSC averages half a dot short of 341 * 262 per frame.
+    pub fn frame_rate(&self) -> f64 {
+        let (_, ppu_divider) = self.clock_dividers();
+        let dots_per_frame = match self {
+            Region::Ntsc => 341.0 * 262.0 - 0.5,
+            Region::Pal | Region::Dendy => 341.0 * 312.0,
+        };
+        self.master_clock_hz() / (ppu_divider as f64 * dots_per_frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rates() {
+        assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.001);
+        assert!((Region::Pal.frame_rate() - 50.0070).abs() < 0.001);
+        assert_eq!(Region::Dendy.frame_rate(), Region::Pal.frame_rate());
+    }
+}
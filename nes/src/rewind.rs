@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+/// How much history a [`RewindBuffer`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewindConfig {
+    /// The most memory snapshots may take up, in bytes. The oldest are dropped to stay under it.
+    pub memory_budget: usize,
+    /// Frames between snapshots. Rewinding steps back in multiples of this.
+    pub snapshot_interval: u64,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: 64 * 1024 * 1024,
+            snapshot_interval: 1,
+        }
+    }
+}
+
+/// A memory-bounded history of snapshots. Only the newest is kept whole; each older one is stored as the XOR
+/// against the snapshot after it, run-length encoded. Consecutive frames differ in few bytes, so that's mostly runs
+/// of zeros.
+#[derive(Clone)]
+pub struct RewindBuffer {
+    config: RewindConfig,
+    newest: Option<(u64, Vec<u8>)>,
+    /// Deltas back from each snapshot to the one before it, oldest first.
+    deltas: VecDeque<Delta>,
+    delta_bytes: usize,
+}
+
+#[derive(Clone)]
+struct Delta {
+    /// The frame of the older snapshot, and its length.
+    frame: u64,
+    length: usize,
+    runs: Vec<u8>,
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> Self {
+        Self {
+            config,
+            newest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    pub fn config(&self) -> RewindConfig {
+        self.config
+    }
+
+    /// Whether a snapshot is due on this frame.
+    pub fn is_due(&self, frame: u64) -> bool {
+        frame.is_multiple_of(self.config.snapshot_interval.max(1))
+    }
+
+    /// Records the snapshot taken on `frame`.
+    pub fn push(&mut self, frame: u64, snapshot: Vec<u8>) {
+        if let Some((newest_frame, newest)) = self.newest.take() {
+            let delta = Delta {
+                frame: newest_frame,
+                length: newest.len(),
+                runs: encode_delta(&snapshot, &newest),
+            };
+            self.delta_bytes += delta.runs.len();
+            self.deltas.push_back(delta);
+        }
+        self.newest = Some((frame, snapshot));
+
+        while self.memory_used() > self.config.memory_budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.delta_bytes -= delta.runs.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Steps back to the newest snapshot taken on or before `frame`, or the oldest one held if they're all later,
+    /// and returns it. Everything after it is dropped, so recording carries on from there.
+    pub fn rewind_to(&mut self, frame: u64) -> Option<(u64, &[u8])> {
+        let (newest_frame, newest) = self.newest.as_mut()?;
+        while *newest_frame > frame {
+            let Some(delta) = self.deltas.pop_back() else {
+                break;
+            };
+            self.delta_bytes -= delta.runs.len();
+            apply_delta(newest, &delta.runs, delta.length);
+            *newest_frame = delta.frame;
+        }
+        Some((*newest_frame, newest.as_slice()))
+    }
+
+    /// The number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.newest.iter().count() + self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// The frame of the oldest snapshot held, i.e. how far back it's possible to go.
+    pub fn oldest_frame(&self) -> Option<u64> {
+        match self.deltas.front() {
+            Some(delta) => Some(delta.frame),
+            None => self.newest.as_ref().map(|(frame, _)| *frame),
+        }
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.newest
+            .as_ref()
+            .map_or(0, |(_, snapshot)| snapshot.len())
+            + self.delta_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+}
+
+/// Encodes `older` relative to `newer` as alternating runs: a count of bytes that match, then a count of bytes that
+/// differ followed by their XOR. Counts are LEB128 varints. Bytes past the end of either side count as zero.
+fn encode_delta(newer: &[u8], older: &[u8]) -> Vec<u8> {
+    let length = newer.len().max(older.len());
+    let xor = |i: usize| newer.get(i).unwrap_or(&0) ^ older.get(i).unwrap_or(&0);
+
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < length {
+        let start = i;
+        while i < length && xor(i) == 0 {
+            i += 1;
+        }
+        write_varint(&mut runs, i - start);
+
+        let start = i;
+        while i < length && xor(i) != 0 {
+            i += 1;
+        }
+        write_varint(&mut runs, i - start);
+        runs.extend((start..i).map(xor));
+    }
+    runs
+}
+
+/// Turns `snapshot` into the older snapshot `runs` was encoded from, which is `length` bytes long.
+fn apply_delta(snapshot: &mut Vec<u8>, runs: &[u8], length: usize) {
+    snapshot.resize(snapshot.len().max(length), 0);
+
+    let mut position = 0;
+    let mut runs = runs.iter().copied();
+    while let Some(matching) = read_varint(&mut runs) {
+        position += matching;
+        let differing = read_varint(&mut runs).unwrap_or(0);
+        for byte in &mut snapshot[position..position + differing] {
+            *byte ^= runs.next().unwrap_or(0);
+        }
+        position += differing;
+    }
+    snapshot.truncate(length);
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(frame: u64) -> Vec<u8> {
+        let mut snapshot = vec![0x55; 4096];
+        snapshot[frame as usize % 4096] = frame as u8;
+        snapshot[100..104].copy_from_slice(&(frame as u32).to_le_bytes());
+        // Vary the length too, as sprite slices do in save states.
+        snapshot.truncate(4096 - frame as usize % 3);
+        snapshot
+    }
+
+    #[test]
+    fn deltas_round_trip() {
+        let newer = snapshot(1000);
+        for older in [snapshot(999), snapshot(7), vec![], vec![0; 5000]] {
+            let runs = encode_delta(&newer, &older);
+            let mut decoded = newer.clone();
+            apply_delta(&mut decoded, &runs, older.len());
+            assert_eq!(decoded, older);
+        }
+        assert!(encode_delta(&snapshot(2), &snapshot(1)).len() < 32);
+    }
+
+    #[test]
+    fn rewinds_to_earlier_snapshots() {
+        let mut buffer = RewindBuffer::new(RewindConfig::default());
+        assert_eq!(buffer.rewind_to(0), None);
+        for frame in 0..100 {
+            buffer.push(frame, snapshot(frame));
+        }
+        assert_eq!(buffer.len(), 100);
+
+        assert_eq!(buffer.rewind_to(90), Some((90, snapshot(90).as_slice())));
+        assert_eq!(buffer.rewind_to(90), Some((90, snapshot(90).as_slice())));
+        assert_eq!(buffer.len(), 91);
+
+        // Recording picks up from where it stepped back to.
+        buffer.push(91, snapshot(500));
+        assert_eq!(buffer.rewind_to(91), Some((91, snapshot(500).as_slice())));
+        assert_eq!(buffer.rewind_to(0), Some((0, snapshot(0).as_slice())));
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn stays_within_the_memory_budget() {
+        let config = RewindConfig {
+            memory_budget: 4096 + 20 * 16,
+            snapshot_interval: 4,
+        };
+        let mut buffer = RewindBuffer::new(config);
+        for frame in (0..400).step_by(4) {
+            assert!(buffer.is_due(frame));
+            buffer.push(frame, snapshot(frame));
+            assert!(buffer.memory_used() <= config.memory_budget);
+        }
+        assert!(!buffer.is_due(401));
+
+        // The oldest snapshots were dropped, so going all the way back stops at the oldest one left.
+        let oldest = buffer.oldest_frame().unwrap();
+        assert!(oldest > 0);
+        assert_eq!(
+            buffer.rewind_to(0),
+            Some((oldest, snapshot(oldest).as_slice()))
+        );
+    }
+}
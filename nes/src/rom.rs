@@ -1,3 +1,5 @@
+use crate::{region::Region, save_state::fnv1a};
+
 const INES_HEADER_LENGTH: usize = 16;
 const INES_TRAINER_LENGTH: usize = 512;
 const INES_PRG_ROM_UNITS: usize = 16384;
@@ -15,12 +17,31 @@ impl INesHeader {
         }
     }
 
-    pub fn prg_rom_size(&self) -> usize {
-        self.bytes[4] as usize * INES_PRG_ROM_UNITS
+    /// The PRG ROM size in bytes, or `None` if the header asks for more than can be addressed.
+    pub fn prg_rom_size(&self) -> Option<usize> {
+        self.rom_size(self.bytes[4], self.bytes[9] & 0x0F, INES_PRG_ROM_UNITS)
+    }
+
+    /// The CHR ROM size in bytes, or `None` if the header asks for more than can be addressed.
+    pub fn chr_rom_size(&self) -> Option<usize> {
+        self.rom_size(self.bytes[5], self.bytes[9] >> 4, INES_CHR_ROM_UNITS)
     }
 
-    pub fn chr_rom_size(&self) -> usize {
-        self.bytes[5] as usize * INES_CHR_ROM_UNITS
+    /// NES 2.0 extends the size bytes with a high nibble in byte 9. A high nibble of $F switches the low byte to an
+    /// exponent-multiplier form instead: 2^E * (MM * 2 + 1) bytes.
+    fn rom_size(&self, low_byte: u8, high_nibble: u8, units: usize) -> Option<usize> {
+        if !self.is_ines_2_header() {
+            return Some(low_byte as usize * units);
+        }
+
+        match high_nibble {
+            0x0F => {
+                let exponent = (low_byte >> 2) as u32;
+                let multiplier = (low_byte & 0x03) as usize * 2 + 1;
+                2usize.checked_pow(exponent)?.checked_mul(multiplier)
+            }
+            _ => ((high_nibble as usize) << 8 | low_byte as usize).checked_mul(units),
+        }
     }
 
     pub fn mirroring(&self) -> Mirroring {
@@ -42,7 +63,11 @@ impl INesHeader {
     }
 
     pub fn mapper_number(&self) -> u16 {
-        (self.bytes[7] as u16) & 0xF0 | ((self.bytes[6] as u16) & 0xF0) >> 4
+        let mapper_number = (self.bytes[7] as u16) & 0xF0 | ((self.bytes[6] as u16) & 0xF0) >> 4;
+        match self.is_ines_2_header() {
+            true => ((self.bytes[8] as u16) & 0x0F) << 8 | mapper_number,
+            false => mapper_number,
+        }
     }
 
     pub fn console_type(&self) -> ConsoleType {
@@ -58,6 +83,21 @@ impl INesHeader {
     pub fn is_ines_2_header(&self) -> bool {
         self.bytes[7] & 0x0C == 8
     }
+
+    /// The CPU/PPU timing from an NES 2.0 header. Multi-region games run as NTSC, as does anything with an iNES 1.0
+    /// header, whose TV system bit is too often garbage to trust.
+    pub fn region(&self) -> Region {
+        if !self.is_ines_2_header() {
+            return Region::Ntsc;
+        }
+
+        match self.bytes[12] & 0x03 {
+            0 | 2 => Region::Ntsc,
+            1 => Region::Pal,
+            3 => Region::Dendy,
+            _ => unreachable!(),
+        }
+    }
 }
 
 pub struct RomFile {
@@ -76,6 +116,11 @@ pub enum RomLoadError {
 }
 
 impl RomFile {
+    /// Identifies the game by its PRG and CHR ROM, ignoring the header, whose details are often patched.
+    pub fn hash(&self) -> u64 {
+        fnv1a(&[&self.prg_rom, &self.chr_rom])
+    }
+
     pub fn load(bytes: Vec<u8>) -> Result<RomFile, RomLoadError> {
         if bytes.len() < 16 {
             return Err(RomLoadError::MalformedRomFile);
@@ -86,9 +131,6 @@ impl RomFile {
 
         let header = INesHeader::new(bytes[0..16].try_into().unwrap());
 
-        if header.is_ines_2_header() {
-            return Err(RomLoadError::UnsupportedFormat);
-        }
         if header.mapper_number() != 0 {
             return Err(RomLoadError::UnsupportedMapper);
         }
@@ -98,7 +140,10 @@ impl RomFile {
 
         let mut cursor = INES_HEADER_LENGTH;
         let mut consume_bytes = |n: usize| -> Result<Box<[u8]>, RomLoadError> {
-            match bytes.get(cursor..cursor + n) {
+            let end = cursor
+                .checked_add(n)
+                .ok_or(RomLoadError::MalformedRomFile)?;
+            match bytes.get(cursor..end) {
                 Some(slice) => {
                     let bytes: Box<[u8]> = Box::from(slice);
                     cursor += n;
@@ -113,8 +158,14 @@ impl RomFile {
         } else {
             None
         };
-        let prg_rom = consume_bytes(header.prg_rom_size())?;
-        let chr_rom = consume_bytes(header.chr_rom_size())?;
+        let prg_rom_size = header
+            .prg_rom_size()
+            .ok_or(RomLoadError::MalformedRomFile)?;
+        let chr_rom_size = header
+            .chr_rom_size()
+            .ok_or(RomLoadError::MalformedRomFile)?;
+        let prg_rom = consume_bytes(prg_rom_size)?;
+        let chr_rom = consume_bytes(chr_rom_size)?;
 
         Ok(RomFile {
             header,
@@ -155,5 +206,69 @@ mod test {
         assert_eq!(rom_file.prg_rom.len(), 16384);
         assert_eq!(rom_file.chr_rom.len(), 8192);
         assert_eq!(rom_file.trainer, None);
+        assert_eq!(rom_file.header.region(), Region::Ntsc);
+    }
+
+    #[test]
+    fn nes_2_header() {
+        // 258 PRG ROM banks, and 2^3 * 3 bytes of CHR ROM in exponent-multiplier form.
+        let mut bytes = [
+            0x4E, 0x45, 0x53, 0x1A, 2, 0x0D, 0, 0x08, 0, 0xF1, 0, 0, 1, 0, 0, 0,
+        ];
+        let header = INesHeader::new(&bytes);
+        assert!(header.is_ines_2_header());
+        assert_eq!(header.prg_rom_size(), Some(258 * INES_PRG_ROM_UNITS));
+        assert_eq!(header.chr_rom_size(), Some(24));
+        assert_eq!(header.region(), Region::Pal);
+
+        bytes[12] = 3;
+        assert_eq!(INesHeader::new(&bytes).region(), Region::Dendy);
+    }
+
+    #[test]
+    fn malformed_headers() {
+        let header = |prg_rom_byte, size_nibbles| {
+            let mut bytes = vec![
+                0x4E,
+                0x45,
+                0x53,
+                0x1A,
+                prg_rom_byte,
+                0,
+                0,
+                0x08,
+                0,
+                size_nibbles,
+            ];
+            bytes.resize(16 + 64, 0);
+            bytes
+        };
+
+        // 2^63 * 7 bytes of PRG ROM in exponent-multiplier form overflows.
+        let overflowing = header(0xFF, 0x0F);
+        assert_eq!(
+            INesHeader::new(overflowing[0..16].try_into().unwrap()).prg_rom_size(),
+            None
+        );
+        assert!(matches!(
+            RomFile::load(overflowing),
+            Err(RomLoadError::MalformedRomFile)
+        ));
+
+        // 2^63 bytes fits in a 64-bit size, but isn't there.
+        assert!(matches!(
+            RomFile::load(header(0xFC, 0x0F)),
+            Err(RomLoadError::MalformedRomFile)
+        ));
+
+        // Nor is a whole 16 KB bank.
+        assert!(matches!(
+            RomFile::load(header(1, 0)),
+            Err(RomLoadError::MalformedRomFile)
+        ));
+        assert!(matches!(
+            RomFile::load(vec![0x4E, 0x45, 0x53]),
+            Err(RomLoadError::MalformedRomFile)
+        ));
     }
 }
@@ -0,0 +1,207 @@
+//! The binary save state format. A state is a header (magic, format version and a hash of the ROM it was taken on)
+//! followed by each component's fields in a fixed order, little-endian. There's no per-field tagging: any change to
+//! what a component saves must bump `FORMAT_VERSION`.
+
+pub const MAGIC: [u8; 4] = *b"NESS";
+pub const FORMAT_VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    /// The data doesn't start with the save state magic.
+    NotASaveState,
+    /// The state was written by a different version of the format.
+    UnsupportedVersion(u16),
+    /// The state was taken with a different ROM inserted.
+    RomMismatch { expected: u64, found: u64 },
+    /// The data ended early, had bytes left over, or held a value no component could be in.
+    Malformed,
+}
+
+/// Serializes component state into a save state.
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes bytes as they are. The reader has to know how many to expect, e.g. from the size of a RAM.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads component state back out of a save state, in the order it was written.
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    /// The bytes that haven't been read yet.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(SaveStateError::Malformed)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Malformed),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Fills `bytes` from the state, for data written with [`StateWriter::write_bytes`].
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), SaveStateError> {
+        bytes.copy_from_slice(self.take(bytes.len())?);
+        Ok(())
+    }
+}
+
+/// A piece of the console that goes into save states.
+pub(crate) trait Snapshot {
+    fn save(&self, writer: &mut StateWriter);
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+/// The header every save state starts with.
+pub(crate) struct Header {
+    pub rom_hash: u64,
+}
+
+impl Header {
+    pub fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&MAGIC);
+        writer.write_u16(FORMAT_VERSION);
+        writer.write_u64(self.rom_hash);
+    }
+
+    /// Reads a header, checking it was written by this version of the format for the given ROM.
+    pub fn load(reader: &mut StateReader, rom_hash: u64) -> Result<Header, SaveStateError> {
+        let mut magic = [0; 4];
+        reader
+            .read_bytes(&mut magic)
+            .map_err(|_| SaveStateError::NotASaveState)?;
+        if magic != MAGIC {
+            return Err(SaveStateError::NotASaveState);
+        }
+
+        let version = reader.read_u16()?;
+        if version != FORMAT_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let found = reader.read_u64()?;
+        if found != rom_hash {
+            return Err(SaveStateError::RomMismatch {
+                expected: rom_hash,
+                found,
+            });
+        }
+        Ok(Header { rom_hash })
+    }
+}
+
+/// A 64-bit FNV-1a hash, used to tie save states to the ROM they were taken on.
+pub(crate) fn fnv1a(chunks: &[&[u8]]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+    for byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_checks() {
+        let mut writer = StateWriter::new();
+        Header { rom_hash: 0x1234 }.save(&mut writer);
+        let bytes = writer.into_bytes();
+
+        assert!(Header::load(&mut StateReader::new(&bytes), 0x1234).is_ok());
+        assert_eq!(
+            Header::load(&mut StateReader::new(&bytes), 0x5678).err(),
+            Some(SaveStateError::RomMismatch {
+                expected: 0x5678,
+                found: 0x1234
+            })
+        );
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Header::load(&mut StateReader::new(&future), 0x1234).err(),
+            Some(SaveStateError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+
+        assert_eq!(
+            Header::load(&mut StateReader::new(b"NES\x1A"), 0x1234).err(),
+            Some(SaveStateError::NotASaveState)
+        );
+        assert_eq!(
+            Header::load(&mut StateReader::new(&bytes[..8]), 0x1234).err(),
+            Some(SaveStateError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Fixtures shared by the unit tests. The integration tests have their own copy in `tests/common`.
+
+use crate::{cartridge::Cartridge, nes::NES};
+
+/// A console with nestest inserted, just out of reset.
+pub(crate) fn nestest() -> NES {
+    let bytes = std::fs::read("test-roms/nestest/nestest.nes").unwrap();
+    let mut nes = NES::new();
+    nes.insert_cartridge(<dyn Cartridge>::load(bytes).unwrap());
+    nes
+}
@@ -0,0 +1,129 @@
+use nes::{cartridge::Cartridge, nes::NES};
+
+// blargg's test ROMs report through $6000: $80 while running, $81 when the ROM needs a reset, and the result code
+// once finished (0 means passed). $6001-$6003 hold the signature DE B0 61 once that protocol is active, and a
+// null-terminated message follows from $6004.
+const STATUS_ADDRESS: u16 = 0x6000;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const MAX_FRAMES: u32 = 60 * 60;
+// After asking for a reset, the ROM wants at least 100 ms before the button is pressed.
+const RESET_DELAY_FRAMES: u32 = 7;
+
+fn run_blargg_test(path: &str) {
+    let bytes = std::fs::read(path).unwrap_or_else(|_| panic!("{path} is missing"));
+    let cartridge = <dyn Cartridge>::load(bytes).unwrap();
+    let mut nes = NES::new();
+    nes.insert_cartridge(cartridge);
+
+    let mut reset_requested = None;
+    for frame in 0..MAX_FRAMES {
+        nes.advance_to_next_frame();
+        assert!(!nes.jammed(), "{path}: CPU jammed");
+
+        let signature = [
+            nes.peek_byte(0x6001),
+            nes.peek_byte(0x6002),
+            nes.peek_byte(0x6003),
+        ];
+        if signature != SIGNATURE {
+            continue;
+        }
+        match nes.peek_byte(STATUS_ADDRESS) {
+            0x80 => {}
+            0x81 => {
+                let requested = *reset_requested.get_or_insert(frame);
+                if frame - requested >= RESET_DELAY_FRAMES {
+                    nes.reset();
+                    reset_requested = None;
+                }
+            }
+            status => {
+                assert_eq!(status, 0, "{path}: {}", read_message(&nes));
+                return;
+            }
+        }
+    }
+
+    panic!("{path}: timed out");
+}
+
+// The 2005 sprite suites predate that protocol: they leave $F8 at 0 while running, then store 1 for a pass or a
+// failure code from 2 up.
+const LEGACY_RESULT_ADDRESS: u16 = 0x00F8;
+
+fn run_legacy_blargg_test(path: &str) {
+    let bytes = std::fs::read(path).unwrap_or_else(|_| panic!("{path} is missing"));
+    let cartridge = <dyn Cartridge>::load(bytes).unwrap();
+    let mut nes = NES::new();
+    nes.insert_cartridge(cartridge);
+
+    for _ in 0..MAX_FRAMES {
+        nes.advance_to_next_frame();
+        assert!(!nes.jammed(), "{path}: CPU jammed");
+
+        let result = nes.peek_byte(LEGACY_RESULT_ADDRESS);
+        if result != 0 {
+            assert_eq!(result, 1, "{path}: failed with code {result}");
+            return;
+        }
+    }
+
+    panic!("{path}: timed out");
+}
+
+fn read_message(nes: &NES) -> String {
+    (0x6004..0x7000)
+        .map(|address| nes.peek_byte(address))
+        .take_while(|&byte| byte != 0)
+        .map(char::from)
+        .collect()
+}
+
+// blargg's suites are freely redistributable, but they haven't been vendored into test-roms yet. Until they are, copy
+// them from blargg's test suite into test-roms (keeping each suite's directory name) and run with
+// `cargo test -- --ignored`; once a suite is checked in, drop the `ignore` for it.
+macro_rules! blargg_tests {
+    ($runner:ident { $($name:ident => $path:literal,)* }) => {
+        $(
+            #[test]
+            #[ignore = "test ROM not vendored yet"]
+            fn $name() {
+                $runner(concat!("test-roms/", $path));
+            }
+        )*
+    };
+}
+
+blargg_tests! { run_blargg_test {
+    vbl_basics => "ppu_vbl_nmi/01-vbl_basics.nes",
+    vbl_set_time => "ppu_vbl_nmi/02-vbl_set_time.nes",
+    vbl_clear_time => "ppu_vbl_nmi/03-vbl_clear_time.nes",
+    nmi_control => "ppu_vbl_nmi/04-nmi_control.nes",
+    nmi_timing => "ppu_vbl_nmi/05-nmi_timing.nes",
+    suppression => "ppu_vbl_nmi/06-suppression.nes",
+    nmi_on_timing => "ppu_vbl_nmi/07-nmi_on_timing.nes",
+    nmi_off_timing => "ppu_vbl_nmi/08-nmi_off_timing.nes",
+    even_odd_frames => "ppu_vbl_nmi/09-even_odd_frames.nes",
+    even_odd_timing => "ppu_vbl_nmi/10-even_odd_timing.nes",
+    ppu_open_bus => "ppu_open_bus/ppu_open_bus.nes",
+    ppu_read_buffer => "ppu_read_buffer/test_ppu_read_buffer.nes",
+}}
+
+blargg_tests! { run_legacy_blargg_test {
+    sprite_hit_basics => "sprite_hit_tests_2005.10.05/01.basics.nes",
+    sprite_hit_alignment => "sprite_hit_tests_2005.10.05/02.alignment.nes",
+    sprite_hit_corners => "sprite_hit_tests_2005.10.05/03.corners.nes",
+    sprite_hit_flip => "sprite_hit_tests_2005.10.05/04.flip.nes",
+    sprite_hit_left_clip => "sprite_hit_tests_2005.10.05/05.left_clip.nes",
+    sprite_hit_right_edge => "sprite_hit_tests_2005.10.05/06.right_edge.nes",
+    sprite_hit_screen_bottom => "sprite_hit_tests_2005.10.05/07.screen_bottom.nes",
+    sprite_hit_double_height => "sprite_hit_tests_2005.10.05/08.double_height.nes",
+    sprite_hit_timing_basics => "sprite_hit_tests_2005.10.05/09.timing_basics.nes",
+    sprite_hit_timing_order => "sprite_hit_tests_2005.10.05/10.timing_order.nes",
+    sprite_hit_edge_timing => "sprite_hit_tests_2005.10.05/11.edge_timing.nes",
+    sprite_overflow_basics => "sprite_overflow_tests/1.Basics.nes",
+    sprite_overflow_details => "sprite_overflow_tests/2.Details.nes",
+    sprite_overflow_timing => "sprite_overflow_tests/3.Timing.nes",
+    sprite_overflow_obscure => "sprite_overflow_tests/4.Obscure.nes",
+    sprite_overflow_emulator => "sprite_overflow_tests/5.Emulator.nes",
+}}
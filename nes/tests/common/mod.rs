@@ -0,0 +1,17 @@
+//! Fixtures shared by the integration tests.
+
+use nes::{cartridge::Cartridge, nes::NES};
+
+/// The nestest ROM's bytes, for tests that tamper with them.
+#[allow(dead_code)]
+pub fn nestest_bytes() -> Vec<u8> {
+    std::fs::read("test-roms/nestest/nestest.nes").unwrap()
+}
+
+/// A console with nestest inserted, just out of reset.
+#[allow(dead_code)]
+pub fn nestest() -> NES {
+    let mut nes = NES::new();
+    nes.insert_cartridge(<dyn Cartridge>::load(nestest_bytes()).unwrap());
+    nes
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::nestest;
+use nes::rewind::RewindConfig;
+
+#[test]
+fn lag_frames_and_polls() {
+    let mut nes = nestest();
+    let results: Vec<_> = (0..20).map(|_| nes.advance_to_next_frame()).collect();
+
+    // nestest spends its first few frames setting up, then reads the controllers once a frame from its NMI handler.
+    assert!(results[0].lag);
+    assert!(results[0].polls.is_empty());
+    for result in &results[10..] {
+        assert!(!result.lag);
+        assert_eq!(result.polls.len(), 1);
+        assert_eq!(result.polls[0].scanline, 241);
+    }
+    assert_eq!(
+        nes.lag_frame_count(),
+        results.iter().filter(|result| result.lag).count() as u64
+    );
+    assert_eq!(results[19].frame, 20);
+
+    // Running ahead doesn't change what's reported about the real frame.
+    let mut ahead = nestest();
+    ahead.set_run_ahead_frames(2);
+    for result in &results {
+        assert_eq!(&ahead.advance_to_next_frame(), result);
+    }
+    assert_eq!(ahead.lag_frame_count(), nes.lag_frame_count());
+}
+
+#[test]
+fn rewinding_restores_the_lag_frame_count() {
+    let mut nes = nestest();
+    nes.enable_rewind(RewindConfig::default());
+    for _ in 0..3 {
+        nes.advance_to_next_frame();
+    }
+    let lag_frames = nes.lag_frame_count();
+    assert!(lag_frames > 0);
+
+    nes.rewind(3).unwrap();
+    assert_eq!(nes.lag_frame_count(), 0);
+    for _ in 0..3 {
+        nes.advance_to_next_frame();
+    }
+    assert_eq!(nes.lag_frame_count(), lag_frames);
+}
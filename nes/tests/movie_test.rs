@@ -0,0 +1,96 @@
+mod common;
+
+use common::nestest;
+use nes::{
+    input::StandardController,
+    movie::{ConsoleEvent, Movie, MovieError, MovieFrame, MovieStart, Player, Recorder},
+    nes::NES,
+};
+
+/// Moves nestest's menu cursor about, with a reset and a power cycle along the way.
+fn frame(i: usize) -> MovieFrame {
+    MovieFrame {
+        event: match i {
+            20 => Some(ConsoleEvent::SoftReset),
+            35 => Some(ConsoleEvent::Power),
+            _ => None,
+        },
+        port_a: StandardController {
+            down: i % 8 == 4,
+            ..Default::default()
+        },
+        port_b: StandardController::default(),
+    }
+}
+
+fn record(nes: &mut NES, start: MovieStart) -> Movie {
+    let mut recorder = Recorder::new(nes, start, 5).unwrap();
+    for i in 0..50 {
+        recorder.record_frame(nes, frame(i));
+    }
+    recorder.finish()
+}
+
+#[test]
+fn playback_reproduces_the_recording() {
+    let mut nes = nestest();
+    // Run a while first, so the movie has to power cycle to get back to the start.
+    for _ in 0..5 {
+        nes.advance_to_next_frame();
+    }
+    let movie = record(&mut nes, MovieStart::PowerOn);
+    assert_eq!(movie.frames.len(), 50);
+    assert_eq!(movie.checkpoints.len(), 10);
+
+    let mut replay = nestest();
+    movie.play(&mut replay).unwrap();
+    assert_eq!(replay.save_state(), nes.save_state());
+
+    // Through both file formats and back, it still plays in sync.
+    let fm2 = Movie::from_fm2(&movie.to_fm2().unwrap()).unwrap();
+    let bk2 = Movie::from_bk2(&movie.to_bk2().unwrap()).unwrap();
+    for movie in [fm2, bk2] {
+        let mut replay = nestest();
+        movie.play(&mut replay).unwrap();
+        assert_eq!(replay.save_state(), nes.save_state());
+    }
+}
+
+#[test]
+fn movies_can_start_from_a_save_state() {
+    let mut nes = nestest();
+    for _ in 0..10 {
+        nes.advance_to_next_frame();
+    }
+    let state = nes.save_state();
+    let movie = record(&mut nes, MovieStart::SaveState(state));
+
+    let mut replay = nestest();
+    let mut player = Player::new(&movie, &mut replay).unwrap();
+    while player.play_frame(&mut replay).unwrap() {}
+    assert!(player.is_finished());
+    assert_eq!(player.position(), 50);
+    assert_eq!(replay.save_state(), nes.save_state());
+
+    assert!(matches!(movie.to_fm2(), Err(MovieError::Unsupported(_))));
+}
+
+#[test]
+fn checkpoints_catch_desyncs() {
+    let mut movie = record(&mut nestest(), MovieStart::PowerOn);
+    // An extra press moves the cursor one further, changing the RAM from then on.
+    movie.frames[9].port_a.down = true;
+
+    let mut replay = nestest();
+    match movie.play(&mut replay) {
+        Err(MovieError::Desync {
+            frame,
+            expected,
+            found,
+        }) => {
+            assert!(frame > 9);
+            assert_ne!(expected, found);
+        }
+        result => panic!("expected a desync, got {result:?}"),
+    }
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::nestest;
+use nes::input::StandardController;
+
+/// Presses down on frame 3, which moves nestest's menu cursor.
+fn controller_on(frame: usize) -> StandardController {
+    StandardController {
+        down: frame == 3,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn run_ahead_shows_later_frames_without_moving_the_console() {
+    let mut plain = nestest();
+    let mut history = vec![(
+        plain.borrow_frame().color_indices().to_vec(),
+        plain.save_state(),
+    )];
+    for frame in 1..=12 {
+        plain.advance_to_next_frame();
+        plain.update_controller_port_a(&controller_on(frame));
+        history.push((
+            plain.borrow_frame().color_indices().to_vec(),
+            plain.save_state(),
+        ));
+    }
+
+    let mut nes = nestest();
+    nes.set_run_ahead_frames(2);
+    for frame in 1..=10 {
+        nes.advance_to_next_frame();
+        nes.update_controller_port_a(&controller_on(frame));
+        assert_eq!(nes.save_state(), history[frame].1);
+        // The frames run ahead reuse the last real frame's input, so they only match the plain run while the input
+        // holds steady across them.
+        if !(2..=4).contains(&frame) {
+            assert_eq!(nes.borrow_frame().color_indices(), history[frame + 2].0);
+        }
+    }
+
+    let cost = nes.run_ahead_cost();
+    assert_eq!(cost.frames, 2);
+    assert!(cost.total() >= cost.emulation);
+}
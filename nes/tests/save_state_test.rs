@@ -0,0 +1,128 @@
+mod common;
+
+use common::{nestest, nestest_bytes};
+use nes::{cartridge::Cartridge, nes::NES, rewind::RewindConfig, save_state::SaveStateError};
+
+fn run_frames(nes: &mut NES, frames: usize) -> Vec<u16> {
+    for _ in 0..frames {
+        nes.advance_to_next_frame();
+    }
+    nes.borrow_frame().color_indices().to_vec()
+}
+
+#[test]
+fn loading_a_state_replays_exactly() {
+    let mut nes = nestest();
+    run_frames(&mut nes, 10);
+    let state = nes.save_state();
+
+    let first_frame = run_frames(&mut nes, 5);
+    let first_state = nes.save_state();
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.save_state(), state);
+    assert_eq!(run_frames(&mut nes, 5), first_frame);
+    assert_eq!(nes.save_state(), first_state);
+
+    // A fresh console picks up from the state too.
+    let mut other = nestest();
+    other.load_state(&state).unwrap();
+    assert_eq!(run_frames(&mut other, 5), first_frame);
+}
+
+#[test]
+fn bad_states_are_rejected() {
+    let mut nes = nestest();
+    run_frames(&mut nes, 2);
+    let state = nes.save_state();
+    run_frames(&mut nes, 1);
+    let before = nes.save_state();
+
+    assert_eq!(
+        nes.load_state(&state[..state.len() - 1]),
+        Err(SaveStateError::Malformed)
+    );
+    let mut padded = state.clone();
+    padded.push(0);
+    assert_eq!(nes.load_state(&padded), Err(SaveStateError::Malformed));
+    assert_eq!(
+        nes.load_state(b"not a state"),
+        Err(SaveStateError::NotASaveState)
+    );
+    assert_eq!(nes.save_state(), before);
+
+    // Same layout, different game.
+    let mut other_game = NES::new();
+    let mut bytes = nestest_bytes();
+    bytes[16] ^= 0xFF;
+    other_game.insert_cartridge(<dyn Cartridge>::load(bytes).unwrap());
+    assert!(matches!(
+        other_game.load_state(&state),
+        Err(SaveStateError::RomMismatch { .. })
+    ));
+}
+
+#[test]
+fn rewinding_restores_earlier_frames() {
+    let mut nes = nestest();
+    nes.enable_rewind(RewindConfig::default());
+
+    let mut history = vec![(
+        nes.borrow_frame().color_indices().to_vec(),
+        nes.save_state(),
+    )];
+    for _ in 0..20 {
+        let frame = run_frames(&mut nes, 1);
+        history.push((frame, nes.save_state()));
+    }
+    assert_eq!(nes.frame_count(), 20);
+
+    assert_eq!(nes.rewind(5), Ok(5));
+    assert_eq!(nes.frame_count(), 15);
+    assert_eq!(nes.borrow_frame().color_indices(), history[15].0);
+    assert_eq!(nes.save_state(), history[15].1);
+
+    // Resuming plays the same frames again.
+    assert_eq!(run_frames(&mut nes, 5), history[20].0);
+    assert_eq!(nes.save_state(), history[20].1);
+
+    // There's nothing before power on.
+    assert_eq!(nes.rewind(100), Ok(20));
+    assert_eq!(nes.save_state(), history[0].1);
+    assert_eq!(nes.rewind(1), Ok(0));
+}
+
+#[test]
+fn rewinding_with_a_snapshot_interval() {
+    let mut nes = nestest();
+    nes.enable_rewind(RewindConfig {
+        snapshot_interval: 4,
+        ..RewindConfig::default()
+    });
+    run_frames(&mut nes, 10);
+    let snapshots = nes.rewind_buffer().unwrap().len();
+    assert_eq!(snapshots, 3);
+
+    // Frame 7 wasn't kept, so it goes back to frame 4.
+    assert_eq!(nes.rewind(3), Ok(6));
+    assert_eq!(nes.frame_count(), 4);
+}
+
+#[test]
+fn inserting_a_cartridge_starts_rewind_over() {
+    let mut nes = nestest();
+    nes.enable_rewind(RewindConfig::default());
+    run_frames(&mut nes, 5);
+
+    let mut bytes = nestest_bytes();
+    bytes[16] ^= 0xFF;
+    nes.insert_cartridge(<dyn Cartridge>::load(bytes).unwrap());
+    let frame_count = nes.frame_count();
+    assert_eq!(nes.rewind_buffer().unwrap().len(), 1);
+
+    // The old cartridge's snapshots are gone, so there's nothing to step back to.
+    let state = nes.save_state();
+    assert_eq!(nes.rewind(3), Ok(0));
+    assert_eq!(nes.frame_count(), frame_count);
+    assert_eq!(nes.save_state(), state);
+}
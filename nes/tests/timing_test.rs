@@ -0,0 +1,139 @@
+use nes::{cartridge::Cartridge, nes::NES};
+
+/// Builds an NROM image whose 16 KB of PRG ROM starts with `program`, with the reset vector pointing at it.
+fn nrom_with_program(program: &[u8]) -> Box<dyn Cartridge> {
+    let mut bytes = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg_rom = vec![0xEA; 16384];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0xC0;
+    bytes.extend(prg_rom);
+    bytes.extend([0; 8192]);
+    <dyn Cartridge>::load(bytes).unwrap()
+}
+
+#[test]
+fn oam_dma_stalls_the_cpu() {
+    let program = [
+        0x85, 0x00, // STA $00
+        0xA9, 0xAB, // LDA #$AB
+        0x8D, 0x00, 0x02, // STA $0200
+        0xA9, 0x05, // LDA #$05
+        0x8D, 0x03, 0x20, // STA $2003
+        0xA9, 0x02, // LDA #$02
+        0x8D, 0x14, 0x40, // STA $4014
+        0xEA, // NOP
+        0xEA, // NOP
+    ];
+    let mut nes = NES::new();
+    nes.insert_cartridge(nrom_with_program(&program));
+
+    let mut dma_lengths = Vec::new();
+    // Starting after the 3-cycle STA puts the second transfer on the other cycle parity.
+    for entry in [0xC000, 0xC002] {
+        nes.set_pc(entry);
+        while nes.get_pc() != 0xC00E {
+            nes.tick();
+        }
+        let start = nes.current_state().cycle_number;
+        nes.tick();
+        dma_lengths.push(nes.current_state().cycle_number - start - 4);
+    }
+    dma_lengths.sort();
+    assert_eq!(dma_lengths, [513, 514]);
+
+    // The transfer started at OAMADDR 5 and wrapped back around to it.
+    assert_eq!(nes.peek_byte(0x2004), 0xAB);
+}
+
+/// Where the status race programs start.
+const DELAY_3: u16 = 0xC000;
+const SET_X: u16 = 0xC002;
+const LDA_STATUS: u16 = 0xC004;
+const LDA_STATUS_X: u16 = 0xC007;
+const NOP: u16 = 0xC100;
+
+/// Runs a status load that starts on CPU cycle `start`, counted from the end of the reset sequence. Returns whether
+/// the load saw the vblank flag, and whether the flag is set a few cycles later.
+fn status_race(load: u16, start: u64) -> (bool, bool) {
+    let program = [
+        0xA5, 0x00, // LDA $00
+        0xA2, 0x03, // LDX #$03
+        0xAD, 0x02, 0x20, // LDA $2002
+        0xBD, 0xFF, 0x20, // LDA $20FF,X
+    ];
+    let mut nes = NES::new();
+    nes.insert_cartridge(nrom_with_program(&program));
+    let reset_cycles = nes.current_state().cycle_number;
+    let elapsed = |nes: &NES| nes.current_state().cycle_number - reset_cycles;
+
+    let run = |nes: &mut NES, pc| {
+        nes.set_pc(pc);
+        nes.tick();
+    };
+    run(&mut nes, SET_X);
+    if (start - elapsed(&nes)) % 2 == 1 {
+        run(&mut nes, DELAY_3);
+    }
+    while elapsed(&nes) < start {
+        run(&mut nes, NOP);
+    }
+    assert_eq!(elapsed(&nes), start);
+
+    run(&mut nes, load);
+    let read = nes.current_state().a & 0x80 != 0;
+    for _ in 0..5 {
+        run(&mut nes, NOP);
+    }
+    (read, nes.peek_byte(0x2002) & 0x80 != 0)
+}
+
+#[test]
+fn ppu_reads_see_the_ppu_at_the_access_cycle() {
+    // A read on CPU cycle n sees the PPU just before it runs dot 3n. The vblank flag is set by dot 1 of line 241
+    // (dot 82182), so the first read to see it is on cycle 27395, and a read on cycle 27394 lands exactly on that
+    // dot and stops it being set at all.
+    let vblank_cycle = (241 * 341 + 1) / 3 + 1;
+    let expected = [(false, true), (false, false), (true, false)];
+
+    // LDA absolute reads its operand on its fourth cycle.
+    let results: Vec<_> = (vblank_cycle - 2..=vblank_cycle)
+        .map(|read_cycle| status_race(LDA_STATUS, read_cycle - 3))
+        .collect();
+    assert_eq!(results, expected);
+
+    // Crossing a page with LDA absolute,X pushes the read to the fifth cycle.
+    let results: Vec<_> = (vblank_cycle - 2..=vblank_cycle)
+        .map(|read_cycle| status_race(LDA_STATUS_X, read_cycle - 4))
+        .collect();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn read_modify_write_instructions_write_twice() {
+    let program = [
+        0xA9, 0x20, // LDA #$20
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x55, // LDA #$55
+        0x8D, 0x07, 0x20, // STA $2007
+        0xA9, 0x20, // LDA #$20
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xAD, 0x07, 0x20, // LDA $2007
+        0xEE, 0x07, 0x20, // INC $2007
+        0xEA, // NOP
+    ];
+    let mut nes = NES::new();
+    nes.insert_cartridge(nrom_with_program(&program));
+    while nes.get_pc() != 0xC01F {
+        nes.tick();
+    }
+
+    // INC reads back the $55 left in the read buffer, writes it straight back and then writes $56, and each of those
+    // accesses moves the VRAM address on.
+    assert_eq!(nes.peek_ppu_memory(0x2002), 0x55);
+    assert_eq!(nes.peek_ppu_memory(0x2003), 0x56);
+}
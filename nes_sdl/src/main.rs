@@ -1,13 +1,15 @@
 extern crate sdl2;
 
 use nes::cartridge::Cartridge;
-use nes::frame::Frame;
+use nes::frame::{Frame, PixelFormat};
 use nes::input::StandardController;
 use nes::nes::NES;
+use nes::palettes::Palette;
+use nes::rewind::RewindConfig;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::{Color, PixelFormatEnum};
-use sdl2::render::{Texture, TextureAccess, UpdateTextureError};
+use sdl2::render::{Texture, TextureAccess};
 use sdl2::render::{TextureCreator, TextureValueError};
 use sdl2::video::{Window, WindowBuildError, WindowContext};
 use sdl2::VideoSubsystem;
@@ -27,6 +29,19 @@ pub fn main() -> Result<(), Box<dyn error::Error>> {
 
     let mut nes = NES::new();
     nes.insert_cartridge(cartridge);
+    nes.enable_rewind(RewindConfig::default());
+
+    if let Some(palette_path) = args.get(2) {
+        let palette_bytes = std::fs::read(palette_path)?;
+        let palette = match Palette::from_pal_file(&palette_bytes) {
+            Ok(palette) => palette,
+            Err(error) => {
+                eprintln!("Couldn't load palette {palette_path}: {error}");
+                std::process::exit(1);
+            }
+        };
+        nes.set_palette(palette);
+    }
 
     let mut controller: StandardController = Default::default();
 
@@ -40,11 +55,18 @@ pub fn main() -> Result<(), Box<dyn error::Error>> {
 
     let texture_creator = canvas.texture_creator();
     let mut texture = create_texture(&texture_creator)?;
+    let mut pixels = vec![0; Frame::WIDTH * Frame::HEIGHT * PixelFormat::Rgb888.bytes_per_pixel()];
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
 
+    let frame_duration = Duration::from_secs_f64(1.0 / nes.frame_rate());
+    let mut next_frame = Instant::now();
+
+    // Holding backspace plays the game backwards.
+    let mut rewinding = false;
+
     let mut event_pump = sdl_ctx.event_pump()?;
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -54,6 +76,14 @@ pub fn main() -> Result<(), Box<dyn error::Error>> {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
                 Event::KeyDown {
                     keycode: Some(keycode),
                     repeat: false,
@@ -68,22 +98,37 @@ pub fn main() -> Result<(), Box<dyn error::Error>> {
             }
         }
 
-        if !nes.jammed() {
+        if rewinding || !nes.jammed() {
             let start = Instant::now();
 
-            nes.advance_to_next_frame();
-            nes.update_controller_port_a(&controller);
-            let frame = nes.borrow_frame();
-
-            copy_frame_to_texture(&mut texture, frame)?;
+            if rewinding {
+                if let Err(error) = nes.rewind(1) {
+                    eprintln!("Couldn't rewind: {error:?}");
+                }
+            } else {
+                nes.advance_to_next_frame();
+                nes.update_controller_port_a(&controller);
+            }
+            nes.convert_frame(PixelFormat::Rgb888, &mut pixels);
+            texture.update(
+                None,
+                &pixels,
+                Frame::WIDTH * PixelFormat::Rgb888.bytes_per_pixel(),
+            )?;
             canvas.copy(&texture, None, None)?;
             canvas.present();
 
             println!("frame time: {:?}", start.elapsed());
         }
 
-        // TODO: Create a more precise timing mechanism. This doesn't take into account time spent executing.
-        std::thread::sleep(Duration::from_millis(16));
+        // Pace frames against a running deadline so time spent emulating doesn't add up as drift.
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
     }
 
     return Ok(());
@@ -111,11 +156,6 @@ fn create_texture(creator: &TextureCreator<WindowContext>) -> Result<Texture, Te
     )
 }
 
-fn copy_frame_to_texture(texture: &mut Texture, frame: &Frame) -> Result<(), UpdateTextureError> {
-    let pitch = Frame::WIDTH * Frame::BYTES_PER_PIXEL;
-    texture.update(None, frame.data_rgb8(), pitch)
-}
-
 fn update_controller(controller: &mut StandardController, keycode: Keycode, pressed: bool) {
     match keycode {
         Keycode::Up => controller.up = pressed,